@@ -3,9 +3,17 @@ use std::{
     sync::{mpsc::channel, Arc, Mutex},
 };
 
-use criterion::{criterion_group, criterion_main, Criterion};
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion};
 use rayon::iter::{ParallelBridge, ParallelIterator};
-use raytracing_rs::{camera::Camera, color, hit::HitList, point, random::Random, v3};
+use raytracing_rs::{
+    bvh::{BvhBuilder, BvhNode},
+    camera::Camera,
+    color,
+    hit::{Hit, HitList},
+    point,
+    random::Random,
+    v3,
+};
 use threadpool::ThreadPool;
 
 // Image
@@ -16,15 +24,16 @@ const SAMPLES_PER_PIXEL: i32 = 10;
 const MAX_DEPTH: i32 = 10;
 
 struct RayTracing {
-    world: HitList,
+    world: Box<dyn Hit>,
     camera: Camera,
 }
 
 impl RayTracing {
     pub fn new() -> Self {
-        // World
-        let world = HitList::random_scene();
+        Self::with_world(Box::new(HitList::random_scene()))
+    }
 
+    pub fn with_world(world: Box<dyn Hit>) -> Self {
         // Camera
         let look_from = point!(13, 2, 3);
         let look_at = point!(0, 0, 0);
@@ -173,7 +182,7 @@ impl RayTracing {
         contents
     }
 
-    pub fn rayon_parallel_per_ray_render(self) -> String {
+    pub fn rayon_parallel_per_ray_render(&self) -> String {
         let header = format!("P3\n{} {}\n255", IMAGE_WIDTH, IMAGE_HEIGHT);
 
         let pairs = (0..IMAGE_HEIGHT)
@@ -229,12 +238,47 @@ pub fn rayon_parallel_per_ray(c: &mut Criterion) {
     });
 }
 
+pub fn bvh_build(c: &mut Criterion) {
+    let mut group = c.benchmark_group("bvh build");
+    group.bench_function("median", |b| {
+        b.iter_batched(HitList::random_scene, BvhNode::new, BatchSize::SmallInput)
+    });
+    group.bench_function("sah", |b| {
+        b.iter_batched(
+            HitList::random_scene,
+            |list| BvhBuilder::new().build(list),
+            BatchSize::SmallInput,
+        )
+    });
+    group.finish();
+}
+
+pub fn bvh_traversal(c: &mut Criterion) {
+    let mut group = c.benchmark_group("traversal");
+    let linear = RayTracing::new();
+    group.bench_function("linear", |b| {
+        b.iter(|| linear.rayon_parallel_per_ray_render())
+    });
+    let median = RayTracing::with_world(BvhNode::new(HitList::random_scene()));
+    group.bench_function("median bvh", |b| {
+        b.iter(|| median.rayon_parallel_per_ray_render())
+    });
+    let (world, _) = BvhBuilder::new().build(HitList::random_scene());
+    let sah = RayTracing::with_world(world);
+    group.bench_function("sah bvh", |b| {
+        b.iter(|| sah.rayon_parallel_per_ray_render())
+    });
+    group.finish();
+}
+
 criterion_group!(
     benches,
     single_thread,
     multi_thread,
     multi_thread_per_ray,
     rayon_parallel,
-    rayon_parallel_per_ray
+    rayon_parallel_per_ray,
+    bvh_build,
+    bvh_traversal
 );
 criterion_main!(benches);
@@ -6,7 +6,7 @@ use std::{
 };
 use threadpool::ThreadPool;
 
-use raytracing_rs::{camera::Camera, color, hit::HitList, point, random::Random, v3};
+use raytracing_rs::{bvh::BvhNode, camera::Camera, color, hit::HitList, point, random::Random, v3};
 
 // Image
 const DIST: &str = "dist/image.ppm";
@@ -18,7 +18,7 @@ const MAX_DEPTH: i32 = 50;
 
 fn main() {
     // World
-    let world = HitList::random_scene();
+    let world = BvhNode::new(HitList::random_scene());
     let world = Arc::new(world);
 
     // Camera
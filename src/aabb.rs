@@ -0,0 +1,84 @@
+use crate::{point, ray::Ray, vec3::Point};
+
+#[derive(Clone, Copy, Debug)]
+pub struct Aabb {
+    pub minimum: Point,
+    pub maximum: Point,
+}
+
+impl Aabb {
+    pub fn new(minimum: Point, maximum: Point) -> Self {
+        Self { minimum, maximum }
+    }
+
+    pub fn hit(&self, ray: &Ray, mut t_min: f64, mut t_max: f64) -> bool {
+        for axis in 0..3 {
+            let inv_d = 1.0 / ray.direction[axis];
+            let mut t0 = (self.minimum[axis] - ray.origin[axis]) * inv_d;
+            let mut t1 = (self.maximum[axis] - ray.origin[axis]) * inv_d;
+            if inv_d < 0.0 {
+                std::mem::swap(&mut t0, &mut t1);
+            }
+            t_min = if t0 > t_min { t0 } else { t_min };
+            t_max = if t1 < t_max { t1 } else { t_max };
+            if t_max <= t_min {
+                return false;
+            }
+        }
+        true
+    }
+
+    pub fn surrounding(&self, other: &Self) -> Self {
+        let minimum = point!(
+            self.minimum.x.min(other.minimum.x),
+            self.minimum.y.min(other.minimum.y),
+            self.minimum.z.min(other.minimum.z)
+        );
+        let maximum = point!(
+            self.maximum.x.max(other.maximum.x),
+            self.maximum.y.max(other.maximum.y),
+            self.maximum.z.max(other.maximum.z)
+        );
+        Self { minimum, maximum }
+    }
+
+    pub fn padded(&self, delta: f64) -> Self {
+        let mut minimum = self.minimum;
+        let mut maximum = self.maximum;
+        for (min, max) in [
+            (&mut minimum.x, &mut maximum.x),
+            (&mut minimum.y, &mut maximum.y),
+            (&mut minimum.z, &mut maximum.z),
+        ] {
+            if *max - *min < delta {
+                *min -= delta / 2.0;
+                *max += delta / 2.0;
+            }
+        }
+        Self { minimum, maximum }
+    }
+
+    pub fn contains(&self, point: &Point) -> bool {
+        (0..3).all(|axis| (self.minimum[axis]..=self.maximum[axis]).contains(&point[axis]))
+    }
+
+    pub fn surface_area(&self) -> f64 {
+        let d = self.maximum - self.minimum;
+        2.0 * (d.x * d.y + d.y * d.z + d.z * d.x)
+    }
+
+    pub fn centroid(&self) -> Point {
+        0.5 * (self.minimum + self.maximum)
+    }
+
+    pub fn longest_axis(&self) -> usize {
+        let extent = self.maximum - self.minimum;
+        if extent.x > extent.y && extent.x > extent.z {
+            0
+        } else if extent.y > extent.z {
+            1
+        } else {
+            2
+        }
+    }
+}
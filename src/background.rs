@@ -0,0 +1,144 @@
+use std::{f64::consts::PI, sync::Arc};
+
+use crate::{
+    color,
+    distribution::Distribution2D,
+    image::{Image, Wrap},
+    random::Random,
+    sphere::sphere_uv,
+    v3,
+    vec3::{Color, Vec3},
+};
+
+// Radiance seen by rays that leave the scene.
+pub enum Background {
+    Solid(Color),
+    Gradient { bottom: Color, top: Color },
+    Environment(Environment),
+}
+
+impl Background {
+    pub fn new_solid(color: Color) -> Self {
+        Self::Solid(color)
+    }
+
+    pub fn new_gradient(bottom: Color, top: Color) -> Self {
+        Self::Gradient { bottom, top }
+    }
+
+    pub fn sky() -> Self {
+        Self::new_gradient(color!(1, 1, 1), color!(0.5, 0.7, 1))
+    }
+
+    pub fn new_environment(image: Arc<Image>, rotation: f64, intensity: f64) -> Self {
+        Self::Environment(Environment::new(image, rotation, intensity))
+    }
+
+    pub fn value(&self, direction: &Vec3) -> Color {
+        match self {
+            Self::Solid(color) => *color,
+            Self::Gradient { bottom, top } => {
+                let t = 0.5 * (direction.unit().y + 1.0);
+                (1.0 - t) * *bottom + t * *top
+            }
+            Self::Environment(environment) => environment.value(direction),
+        }
+    }
+}
+
+impl Default for Background {
+    fn default() -> Self {
+        Self::sky()
+    }
+}
+
+// Equirectangular map using the same (u, v) layout as `sphere_uv`.
+pub struct Environment {
+    image: Arc<Image>,
+    rotation: f64,
+    intensity: f64,
+    distribution: Distribution2D,
+}
+
+impl Environment {
+    // `rotation` turns the map around +y, in degrees.
+    pub fn new(image: Arc<Image>, rotation: f64, intensity: f64) -> Self {
+        let (width, height) = (image.width(), image.height());
+        // Each pixel takes the brightest of its neighbours, so filtered
+        // lookups never see light where the density is zero. Rows near the
+        // poles cover less solid angle.
+        let luminance = |x: usize, y: usize| image.pixel(x, y).luminance().max(0.0);
+        let func = (0..height)
+            .flat_map(|y| {
+                let sin_theta = (PI * (y as f64 + 0.5) / height as f64).sin();
+                let rows = y.saturating_sub(1)..=(y + 1).min(height - 1);
+                (0..width).map(move |x| {
+                    let columns = [(x + width - 1) % width, x, (x + 1) % width];
+                    let brightest = rows
+                        .clone()
+                        .flat_map(|y| columns.iter().map(move |&x| luminance(x, y)))
+                        .fold(0.0, f64::max);
+                    brightest * sin_theta
+                })
+            })
+            .collect::<Vec<_>>();
+        let distribution = Distribution2D::new(&func, width, height);
+        Self {
+            image,
+            rotation: rotation.to_radians(),
+            intensity,
+            distribution,
+        }
+    }
+
+    pub fn value(&self, direction: &Vec3) -> Color {
+        let (u, v) = self.uv(direction);
+        self.intensity * self.image.bilinear(u, 1.0 - v, Wrap::Horizontal)
+    }
+
+    // Picks a direction proportional to luminance, returning its radiance and
+    // solid angle density.
+    pub fn sample(&self) -> (Vec3, Color, f64) {
+        let ((x, y), pdf) = self.distribution.sample(f64::random(), f64::random());
+        let theta = (1.0 - y) * PI;
+        let phi = (x + self.rotation / (2.0 * PI)) * 2.0 * PI;
+        let sin_theta = theta.sin();
+        let direction = v3!(-phi.cos() * sin_theta, -theta.cos(), phi.sin() * sin_theta);
+        let pdf = if sin_theta > 0.0 {
+            pdf / (2.0 * PI * PI * sin_theta)
+        } else {
+            0.0
+        };
+        (direction, self.value(&direction), pdf)
+    }
+
+    pub fn pdf(&self, direction: &Vec3) -> f64 {
+        let (u, v) = self.uv(direction);
+        let sin_theta = (v * PI).sin();
+        if sin_theta > 0.0 {
+            self.distribution.pdf(u, 1.0 - v) / (2.0 * PI * PI * sin_theta)
+        } else {
+            0.0
+        }
+    }
+
+    // Power reaching a scene of `world_radius`, to weigh it against lights.
+    pub fn power(&self, world_radius: f64) -> f64 {
+        let (width, height) = (self.image.width(), self.image.height());
+        let (mut sum, mut weight) = (0.0, 0.0);
+        for y in 0..height {
+            let sin_theta = (PI * (y as f64 + 0.5) / height as f64).sin();
+            for x in 0..width {
+                sum += self.image.pixel(x, y).luminance().max(0.0) * sin_theta;
+                weight += sin_theta;
+            }
+        }
+        let average = self.intensity * sum / weight;
+        4.0 * PI * PI * world_radius * world_radius * average
+    }
+
+    fn uv(&self, direction: &Vec3) -> (f64, f64) {
+        let (u, v) = sphere_uv(&direction.unit());
+        ((u - self.rotation / (2.0 * PI)).rem_euclid(1.0), v)
+    }
+}
@@ -0,0 +1,75 @@
+use std::sync::Arc;
+
+use crate::{
+    aabb::Aabb,
+    hit::{FlipFace, Hit, HitList, HitRecord},
+    material::Material,
+    ray::Ray,
+    rect::{XYRect, XZRect, YZRect},
+    vec3::{Point, Vec3},
+};
+
+pub struct BoxShape {
+    minimum: Point,
+    maximum: Point,
+    sides: HitList,
+}
+
+impl BoxShape {
+    pub fn new(p0: Point, p1: Point, material: Arc<Material>) -> Box<Self> {
+        let mut sides = HitList::new();
+
+        sides.add(XYRect::new(p0.x, p1.x, p0.y, p1.y, p1.z, material.clone()));
+        sides.add(FlipFace::new(XYRect::new(
+            p0.x,
+            p1.x,
+            p0.y,
+            p1.y,
+            p0.z,
+            material.clone(),
+        )));
+
+        sides.add(XZRect::new(p0.x, p1.x, p0.z, p1.z, p1.y, material.clone()));
+        sides.add(FlipFace::new(XZRect::new(
+            p0.x,
+            p1.x,
+            p0.z,
+            p1.z,
+            p0.y,
+            material.clone(),
+        )));
+
+        sides.add(YZRect::new(p0.y, p1.y, p0.z, p1.z, p1.x, material.clone()));
+        sides.add(FlipFace::new(YZRect::new(
+            p0.y, p1.y, p0.z, p1.z, p0.x, material,
+        )));
+
+        Box::new(Self {
+            minimum: p0,
+            maximum: p1,
+            sides,
+        })
+    }
+}
+
+impl Hit for BoxShape {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        self.sides.hit(ray, t_min, t_max)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(Aabb::new(self.minimum, self.maximum))
+    }
+
+    fn pdf_value(&self, origin: &Point, direction: &Vec3, time: f64) -> f64 {
+        self.sides.pdf_value(origin, direction, time)
+    }
+
+    fn random_direction(&self, origin: &Point, time: f64) -> Option<Vec3> {
+        self.sides.random_direction(origin, time)
+    }
+
+    fn area(&self) -> f64 {
+        self.sides.area()
+    }
+}
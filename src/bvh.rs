@@ -0,0 +1,392 @@
+use std::{
+    cmp::Ordering,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    aabb::Aabb,
+    hit::{Hit, HitList, HitRecord},
+    ray::Ray,
+};
+
+type Bounded = (Aabb, Box<dyn Hit>);
+
+pub(crate) type Split<T> = Result<(Vec<(Aabb, T)>, Vec<(Aabb, T)>), Vec<(Aabb, T)>>;
+
+// A hierarchy over the objects with bounding boxes, beside a flat list of
+// those without, such as infinite planes. Either may be empty.
+pub struct BvhNode {
+    root: Option<Box<dyn Hit>>,
+    unbounded: HitList,
+}
+
+impl BvhNode {
+    pub fn new(list: HitList) -> Box<Self> {
+        let (objects, unbounded) = bounded(list);
+        let root = if objects.is_empty() {
+            None
+        } else {
+            Some(Node::build(objects) as Box<dyn Hit>)
+        };
+        Box::new(Self { root, unbounded })
+    }
+}
+
+impl Hit for BvhNode {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        let bounded = self
+            .root
+            .as_ref()
+            .and_then(|root| root.hit(ray, t_min, t_max));
+        let t_max = bounded.as_ref().map_or(t_max, |rec| rec.t);
+        self.unbounded.hit(ray, t_min, t_max).or(bounded)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        if !self.unbounded.is_empty() {
+            return None;
+        }
+        self.root.as_ref().and_then(|root| root.bounding_box())
+    }
+}
+
+struct Node {
+    left: Box<dyn Hit>,
+    right: Option<Box<dyn Hit>>,
+    bbox: Aabb,
+}
+
+impl Node {
+    fn build(mut objects: Vec<Bounded>) -> Box<Self> {
+        if objects.len() == 1 {
+            let (bbox, left) = objects.pop().unwrap();
+            return Box::new(Self {
+                left,
+                right: None,
+                bbox,
+            });
+        }
+
+        let centroids = objects
+            .iter()
+            .map(|(bbox, _)| Aabb::new(bbox.centroid(), bbox.centroid()))
+            .reduce(|a, b| a.surrounding(&b))
+            .unwrap();
+        let axis = centroids.longest_axis();
+        objects.sort_by(|(a, _), (b, _)| {
+            a.centroid()[axis]
+                .partial_cmp(&b.centroid()[axis])
+                .unwrap_or(Ordering::Equal)
+        });
+
+        let bbox = bounds(&objects);
+        let rest = objects.split_off(objects.len() / 2);
+
+        Box::new(Self {
+            left: Self::subtree(objects),
+            right: Some(Self::subtree(rest)),
+            bbox,
+        })
+    }
+
+    fn subtree(mut objects: Vec<Bounded>) -> Box<dyn Hit> {
+        if objects.len() == 1 {
+            objects.pop().unwrap().1
+        } else {
+            Self::build(objects)
+        }
+    }
+}
+
+impl Hit for Node {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        if !self.bbox.hit(ray, t_min, t_max) {
+            return None;
+        }
+
+        let left = self.left.hit(ray, t_min, t_max);
+        let t_max = left.as_ref().map_or(t_max, |rec| rec.t);
+        let right = self
+            .right
+            .as_ref()
+            .and_then(|right| right.hit(ray, t_min, t_max));
+
+        right.or(left)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(self.bbox)
+    }
+}
+
+const TRAVERSAL_COST: f64 = 0.125;
+const INTERSECTION_COST: f64 = 1.0;
+const PARALLEL_THRESHOLD: usize = 1024;
+
+#[derive(Clone, Copy, Debug)]
+pub struct BvhStats {
+    pub node_count: usize,
+    pub leaf_count: usize,
+    pub depth: usize,
+    pub sah_cost: f64,
+    pub build_time: Duration,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct BvhBuilder {
+    bins: usize,
+    leaf_size: usize,
+}
+
+impl Default for BvhBuilder {
+    fn default() -> Self {
+        Self {
+            bins: 16,
+            leaf_size: 4,
+        }
+    }
+}
+
+impl BvhBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn bins(mut self, bins: usize) -> Self {
+        self.bins = bins.max(2);
+        self
+    }
+
+    pub fn leaf_size(mut self, leaf_size: usize) -> Self {
+        self.leaf_size = leaf_size.max(1);
+        self
+    }
+
+    // Statistics only cover the objects with bounding boxes.
+    pub fn build(&self, list: HitList) -> (Box<BvhNode>, BvhStats) {
+        let start = Instant::now();
+        let (objects, unbounded) = bounded(list);
+        if objects.is_empty() {
+            let stats = BvhStats {
+                node_count: 0,
+                leaf_count: 0,
+                depth: 0,
+                sah_cost: 0.0,
+                build_time: start.elapsed(),
+            };
+            return (
+                Box::new(BvhNode {
+                    root: None,
+                    unbounded,
+                }),
+                stats,
+            );
+        }
+
+        let (root, subtree) = match self.split(objects) {
+            Ok((left, right)) => self.build_branch(left, right),
+            Err(objects) => {
+                let (leaf, subtree) = self.build_leaf(objects);
+                let root = Box::new(Node {
+                    left: leaf,
+                    right: None,
+                    bbox: subtree.bbox,
+                });
+                (root, subtree.wrap())
+            }
+        };
+
+        let stats = BvhStats {
+            node_count: subtree.node_count,
+            leaf_count: subtree.leaf_count,
+            depth: subtree.depth,
+            sah_cost: (TRAVERSAL_COST * subtree.interior_area
+                + INTERSECTION_COST * subtree.leaf_area)
+                / subtree.bbox.surface_area(),
+            build_time: start.elapsed(),
+        };
+        let root = Some(root as Box<dyn Hit>);
+        (Box::new(BvhNode { root, unbounded }), stats)
+    }
+
+    fn build_subtree(&self, objects: Vec<Bounded>) -> (Box<dyn Hit>, Subtree) {
+        match self.split(objects) {
+            Ok((left, right)) => {
+                let (node, subtree) = self.build_branch(left, right);
+                (node, subtree)
+            }
+            Err(objects) => self.build_leaf(objects),
+        }
+    }
+
+    fn build_branch(&self, left: Vec<Bounded>, right: Vec<Bounded>) -> (Box<Node>, Subtree) {
+        let ((left, l), (right, r)) = if left.len() + right.len() > PARALLEL_THRESHOLD {
+            rayon::join(|| self.build_subtree(left), || self.build_subtree(right))
+        } else {
+            (self.build_subtree(left), self.build_subtree(right))
+        };
+
+        let bbox = l.bbox.surrounding(&r.bbox);
+        let subtree = Subtree {
+            bbox,
+            node_count: l.node_count + r.node_count + 1,
+            leaf_count: l.leaf_count + r.leaf_count,
+            depth: l.depth.max(r.depth) + 1,
+            interior_area: l.interior_area + r.interior_area + bbox.surface_area(),
+            leaf_area: l.leaf_area + r.leaf_area,
+        };
+        let node = Box::new(Node {
+            left,
+            right: Some(right),
+            bbox,
+        });
+        (node, subtree)
+    }
+
+    fn build_leaf(&self, mut objects: Vec<Bounded>) -> (Box<dyn Hit>, Subtree) {
+        let bbox = bounds(&objects);
+        let subtree = Subtree {
+            bbox,
+            node_count: 1,
+            leaf_count: 1,
+            depth: 1,
+            interior_area: 0.0,
+            leaf_area: bbox.surface_area() * objects.len() as f64,
+        };
+        let leaf = if objects.len() == 1 {
+            objects.pop().unwrap().1
+        } else {
+            let objects: Vec<Box<dyn Hit>> = objects.into_iter().map(|(_, o)| o).collect();
+            Box::new(HitList::from(objects))
+        };
+        (leaf, subtree)
+    }
+
+    // Splits `objects` in two, or hands them back when they make a better
+    // leaf.
+    pub(crate) fn split<T>(&self, mut objects: Vec<(Aabb, T)>) -> Split<T> {
+        let n = objects.len();
+        if n == 1 {
+            return Err(objects);
+        }
+
+        let area = bounds(&objects).surface_area();
+        let centroids = objects
+            .iter()
+            .map(|(bbox, _)| Aabb::new(bbox.centroid(), bbox.centroid()))
+            .reduce(|a, b| a.surrounding(&b))
+            .unwrap();
+
+        let mut best: Option<(f64, usize, usize)> = None;
+        for axis in 0..3 {
+            let min = centroids.minimum[axis];
+            let extent = centroids.maximum[axis] - min;
+            if extent <= 0.0 {
+                continue;
+            }
+
+            let mut counts = vec![0; self.bins];
+            let mut boxes: Vec<Option<Aabb>> = vec![None; self.bins];
+            for (bbox, _) in objects.iter() {
+                let b = self.bin(bbox.centroid()[axis], min, extent);
+                counts[b] += 1;
+                boxes[b] = Some(boxes[b].map_or(*bbox, |a| a.surrounding(bbox)));
+            }
+
+            let mut right_costs = vec![0.0; self.bins];
+            let mut count = 0;
+            let mut bbox: Option<Aabb> = None;
+            for b in (1..self.bins).rev() {
+                count += counts[b];
+                bbox = merge(bbox, boxes[b]);
+                right_costs[b] = bbox.map_or(0.0, |a| a.surface_area() * count as f64);
+            }
+
+            let mut count = 0;
+            let mut bbox: Option<Aabb> = None;
+            for b in 1..self.bins {
+                count += counts[b - 1];
+                bbox = merge(bbox, boxes[b - 1]);
+                if count == 0 || count == n {
+                    continue;
+                }
+                let left_cost = bbox.map_or(0.0, |a| a.surface_area() * count as f64);
+                let cost = TRAVERSAL_COST + INTERSECTION_COST * (left_cost + right_costs[b]) / area;
+                if !matches!(best, Some((c, _, _)) if c <= cost) {
+                    best = Some((cost, axis, b));
+                }
+            }
+        }
+
+        let leaf_cost = INTERSECTION_COST * n as f64;
+        match best {
+            Some((cost, _, _)) if n <= self.leaf_size && cost >= leaf_cost => Err(objects),
+            Some((_, axis, split)) => {
+                let min = centroids.minimum[axis];
+                let extent = centroids.maximum[axis] - min;
+                Ok(objects
+                    .into_iter()
+                    .partition(|(bbox, _)| self.bin(bbox.centroid()[axis], min, extent) < split))
+            }
+            None if n <= self.leaf_size => Err(objects),
+            None => {
+                let rest = objects.split_off(n / 2);
+                Ok((objects, rest))
+            }
+        }
+    }
+
+    fn bin(&self, centroid: f64, min: f64, extent: f64) -> usize {
+        let b = ((centroid - min) / extent * self.bins as f64) as usize;
+        b.min(self.bins - 1)
+    }
+}
+
+struct Subtree {
+    bbox: Aabb,
+    node_count: usize,
+    leaf_count: usize,
+    depth: usize,
+    interior_area: f64,
+    leaf_area: f64,
+}
+
+impl Subtree {
+    fn wrap(self) -> Self {
+        Self {
+            node_count: self.node_count + 1,
+            depth: self.depth + 1,
+            interior_area: self.interior_area + self.bbox.surface_area(),
+            ..self
+        }
+    }
+}
+
+// Splits off the objects without bounding boxes.
+fn bounded(list: HitList) -> (Vec<Bounded>, HitList) {
+    let mut objects = Vec::new();
+    let mut unbounded = HitList::new();
+    for object in list.into_objects() {
+        match object.bounding_box() {
+            Some(bbox) => objects.push((bbox, object)),
+            None => unbounded.add(object),
+        }
+    }
+    (objects, unbounded)
+}
+
+pub(crate) fn bounds<T>(objects: &[(Aabb, T)]) -> Aabb {
+    objects
+        .iter()
+        .map(|(bbox, _)| *bbox)
+        .reduce(|a, b| a.surrounding(&b))
+        .unwrap()
+}
+
+fn merge(a: Option<Aabb>, b: Option<Aabb>) -> Option<Aabb> {
+    match (a, b) {
+        (Some(a), Some(b)) => Some(a.surrounding(&b)),
+        (a, None) => a,
+        (None, b) => b,
+    }
+}
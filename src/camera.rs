@@ -1,4 +1,5 @@
 use crate::{
+    random::Random,
     ray::Ray,
     vec3::{Point, Vec3},
 };
@@ -12,6 +13,8 @@ pub struct Camera {
     u: Vec3,
     v: Vec3,
     lens_radius: f64,
+    time0: f64,
+    time1: f64,
 }
 
 impl Camera {
@@ -48,14 +51,27 @@ impl Camera {
             u,
             v,
             lens_radius,
+            time0: 0.0,
+            time1: 0.0,
         }
     }
 
+    pub fn with_shutter(mut self, open: f64, close: f64) -> Self {
+        self.time0 = open;
+        self.time1 = close;
+        self
+    }
+
     pub fn get_ray(&self, s: f64, t: f64) -> Ray {
         let rd = self.lens_radius * Vec3::random_in_unit_disk();
         let offset = self.u * rd.x + self.v * rd.y;
         let direction =
             self.lower_left_corner + s * self.horizontal + t * self.vertical - self.origin - offset;
-        Ray::new(self.origin + offset, direction)
+        let time = if self.time1 > self.time0 {
+            f64::random_in(self.time0, self.time1)
+        } else {
+            self.time0
+        };
+        Ray::with_time(self.origin + offset, direction, time)
     }
 }
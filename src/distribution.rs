@@ -0,0 +1,217 @@
+// Piecewise-constant distributions over [0, 1) and [0, 1)^2.
+pub struct Distribution1D {
+    func: Vec<f64>,
+    cdf: Vec<f64>,
+    integral: f64,
+}
+
+impl Distribution1D {
+    pub fn new(func: Vec<f64>) -> Self {
+        assert!(!func.is_empty(), "Distribution1D without values");
+        let n = func.len();
+        let mut cdf = vec![0.0; n + 1];
+        for i in 0..n {
+            cdf[i + 1] = cdf[i] + func[i].abs() / n as f64;
+        }
+        let integral = cdf[n];
+        if integral == 0.0 {
+            // Fall back to uniform so sampling stays well defined.
+            for (i, c) in cdf.iter_mut().enumerate() {
+                *c = i as f64 / n as f64;
+            }
+        } else {
+            for c in cdf.iter_mut() {
+                *c /= integral;
+            }
+        }
+        Self {
+            func,
+            cdf,
+            integral,
+        }
+    }
+
+    pub fn count(&self) -> usize {
+        self.func.len()
+    }
+
+    pub fn integral(&self) -> f64 {
+        self.integral
+    }
+
+    // Returns the sampled position, its density and the segment it fell in.
+    pub fn sample_continuous(&self, u: f64) -> (f64, f64, usize) {
+        let offset = self.find(u);
+        let width = self.cdf[offset + 1] - self.cdf[offset];
+        let du = if width > 0.0 {
+            (u - self.cdf[offset]) / width
+        } else {
+            0.0
+        };
+        let x = ((offset as f64 + du) / self.count() as f64).min(1.0 - f64::EPSILON);
+        (x, self.pdf_at(offset), offset)
+    }
+
+    // Returns the sampled index and its probability.
+    pub fn sample_discrete(&self, u: f64) -> (usize, f64) {
+        let offset = self.find(u);
+        (offset, self.cdf[offset + 1] - self.cdf[offset])
+    }
+
+    pub fn pdf(&self, x: f64) -> f64 {
+        let offset = ((x * self.count() as f64) as usize).min(self.count() - 1);
+        self.pdf_at(offset)
+    }
+
+    pub fn discrete_pdf(&self, index: usize) -> f64 {
+        self.cdf[index + 1] - self.cdf[index]
+    }
+
+    fn pdf_at(&self, offset: usize) -> f64 {
+        if self.integral == 0.0 {
+            1.0
+        } else {
+            self.func[offset].abs() / self.integral
+        }
+    }
+
+    fn find(&self, u: f64) -> usize {
+        let n = self.count();
+        let offset = self.cdf.partition_point(|&c| c <= u).clamp(1, n) - 1;
+        // `u` close to 1 can land on trailing empty segments.
+        (0..=offset)
+            .rev()
+            .find(|&i| self.cdf[i + 1] > self.cdf[i])
+            .unwrap_or(offset)
+    }
+}
+
+// Rows are sampled from the marginal first, then a column within the row.
+pub struct Distribution2D {
+    conditional: Vec<Distribution1D>,
+    marginal: Distribution1D,
+}
+
+impl Distribution2D {
+    // `func` is row-major, `height` rows of `width` values.
+    pub fn new(func: &[f64], width: usize, height: usize) -> Self {
+        assert_eq!(func.len(), width * height, "Distribution2D size mismatch");
+        let conditional = func
+            .chunks_exact(width)
+            .map(|row| Distribution1D::new(row.to_vec()))
+            .collect::<Vec<_>>();
+        let marginal = Distribution1D::new(conditional.iter().map(|d| d.integral()).collect());
+        Self {
+            conditional,
+            marginal,
+        }
+    }
+
+    // Returns (x, y) in [0, 1)^2 and the density with respect to that area.
+    pub fn sample(&self, u0: f64, u1: f64) -> ((f64, f64), f64) {
+        let (y, pdf_y, row) = self.marginal.sample_continuous(u1);
+        let (x, pdf_x, _) = self.conditional[row].sample_continuous(u0);
+        ((x, y), pdf_x * pdf_y)
+    }
+
+    pub fn pdf(&self, x: f64, y: f64) -> f64 {
+        let height = self.marginal.count();
+        let row = ((y * height as f64) as usize).min(height - 1);
+        self.marginal.pdf(y) * self.conditional[row].pdf(x)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(a: f64, b: f64) -> bool {
+        (a - b).abs() < 1e-9
+    }
+
+    // Stratified so every test sees the same spread of samples.
+    fn uniforms(n: usize) -> impl Iterator<Item = f64> {
+        (0..n).map(move |i| (i as f64 + 0.5) / n as f64)
+    }
+
+    #[test]
+    fn pdf_integrates_to_one() {
+        for func in [
+            vec![1.0, 2.0, 3.0, 4.0],
+            vec![0.0, 5.0, 0.0, 0.0, 1.0, 0.0],
+            vec![-2.0, 2.0],
+            vec![0.0, 0.0, 0.0],
+            vec![7.0],
+        ] {
+            let d = Distribution1D::new(func);
+            let n = d.count();
+            let integral: f64 = (0..n).map(|i| d.pdf((i as f64 + 0.5) / n as f64)).sum();
+            assert!(close(integral / n as f64, 1.0));
+            let total: f64 = (0..n).map(|i| d.discrete_pdf(i)).sum();
+            assert!(close(total, 1.0));
+        }
+    }
+
+    #[test]
+    fn samples_follow_the_function() {
+        let d = Distribution1D::new(vec![1.0, 0.0, 3.0, 0.0]);
+        assert!(close(d.integral(), 1.0));
+        let mut counts = [0; 4];
+        for u in uniforms(4000) {
+            let (x, pdf, offset) = d.sample_continuous(u);
+            assert!((0.0..1.0).contains(&x));
+            assert_eq!(offset, (x * 4.0) as usize);
+            assert!(close(pdf, d.pdf(x)));
+            counts[offset] += 1;
+
+            let (index, p) = d.sample_discrete(u);
+            assert_eq!(index, offset);
+            assert!(close(p, d.discrete_pdf(index)));
+        }
+        assert_eq!(counts, [1000, 0, 3000, 0]);
+
+        // The top of the range skips the trailing empty segment.
+        assert_eq!(d.sample_discrete(1.0 - f64::EPSILON).0, 2);
+        assert_eq!(d.sample_continuous(1.0).2, 2);
+    }
+
+    #[test]
+    fn all_zero_is_uniform() {
+        let d = Distribution1D::new(vec![0.0; 4]);
+        assert_eq!(d.integral(), 0.0);
+        assert!(close(d.pdf(0.3), 1.0));
+        assert_eq!(d.sample_discrete(0.6), (2, 0.25));
+    }
+
+    #[test]
+    fn pdf_2d_integrates_to_one() {
+        let (width, height) = (3, 4);
+        let func = [
+            1.0, 2.0, 0.0, //
+            0.0, 0.0, 0.0, //
+            4.0, 4.0, 4.0, //
+            0.5, 0.0, 9.0,
+        ];
+        let d = Distribution2D::new(&func, width, height);
+        let mut integral = 0.0;
+        for y in 0..height {
+            for x in 0..width {
+                let (s, t) = (
+                    (x as f64 + 0.5) / width as f64,
+                    (y as f64 + 0.5) / height as f64,
+                );
+                integral += d.pdf(s, t) / (width * height) as f64;
+            }
+        }
+        assert!(close(integral, 1.0));
+
+        for u1 in uniforms(40) {
+            for u0 in uniforms(30) {
+                let ((x, y), pdf) = d.sample(u0, u1);
+                let cell = (y * height as f64) as usize * width + (x * width as f64) as usize;
+                assert!(func[cell] > 0.0, "sampled empty cell {}", cell);
+                assert!(close(pdf, d.pdf(x, y)));
+            }
+        }
+    }
+}
@@ -0,0 +1,544 @@
+use std::{collections::HashMap, error, fmt, path::Path, sync::Arc};
+
+use ::gltf::{
+    buffer,
+    camera::Projection,
+    image::{self, Format},
+    mesh::Mode,
+    texture::{self, WrappingMode},
+    Document, Node,
+};
+
+use crate::{
+    camera::Camera,
+    color,
+    hit::{Hit, HitList},
+    image::{Edge, Image},
+    instance::Instance,
+    material::Material,
+    mesh::{MeshData, MeshError, TriangleMesh},
+    point,
+    principled::Principled,
+    texture::{ChannelTexture, ImageTexture, ScaledTexture, SolidColor, Texture},
+    transform::Transform,
+    v3,
+    vec3::Color,
+};
+
+#[derive(Debug)]
+pub enum GltfError {
+    Import(::gltf::Error),
+    NoScene,
+    DegenerateNode(usize),
+    Primitive {
+        mesh: usize,
+        primitive: usize,
+        message: String,
+    },
+    Mesh(MeshError),
+}
+
+impl fmt::Display for GltfError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Import(err) => write!(f, "{}", err),
+            Self::NoScene => write!(f, "document has no scene"),
+            Self::DegenerateNode(node) => {
+                write!(f, "node {} has a transform that cannot be inverted", node)
+            }
+            Self::Primitive {
+                mesh,
+                primitive,
+                message,
+            } => write!(f, "mesh {} primitive {}: {}", mesh, primitive, message),
+            Self::Mesh(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl error::Error for GltfError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Self::Import(err) => Some(err),
+            Self::NoScene | Self::DegenerateNode(_) | Self::Primitive { .. } => None,
+            Self::Mesh(err) => Some(err),
+        }
+    }
+}
+
+impl From<::gltf::Error> for GltfError {
+    fn from(err: ::gltf::Error) -> Self {
+        Self::Import(err)
+    }
+}
+
+impl From<MeshError> for GltfError {
+    fn from(err: MeshError) -> Self {
+        Self::Mesh(err)
+    }
+}
+
+pub struct GltfScene {
+    pub world: HitList,
+    pub cameras: Vec<Camera>,
+}
+
+impl GltfScene {
+    // `aspect_ratio` is used for cameras that do not specify their own.
+    pub fn load<P: AsRef<Path>>(path: P, aspect_ratio: f64) -> Result<Self, GltfError> {
+        let (document, buffers, images) = ::gltf::import(path)?;
+        let scene = document
+            .default_scene()
+            .or_else(|| document.scenes().next())
+            .ok_or(GltfError::NoScene)?;
+
+        let mut loader = Loader {
+            document: &document,
+            buffers: &buffers,
+            images: &images,
+            aspect_ratio,
+            meshes: HashMap::new(),
+            materials: HashMap::new(),
+            textures: HashMap::new(),
+            default_material: Material::new_lambertian(color!(0.8)),
+            scene: Self {
+                world: HitList::new(),
+                cameras: Vec::new(),
+            },
+        };
+        for node in scene.nodes() {
+            loader.visit(&node, Transform::identity())?;
+        }
+        Ok(loader.scene)
+    }
+}
+
+struct Loader<'a> {
+    document: &'a Document,
+    buffers: &'a [buffer::Data],
+    images: &'a [image::Data],
+    aspect_ratio: f64,
+    meshes: HashMap<usize, Option<Arc<dyn Hit>>>,
+    materials: HashMap<usize, Arc<Material>>,
+    // By image and whether it holds sRGB colours.
+    textures: HashMap<(usize, bool), Option<Arc<Image>>>,
+    default_material: Arc<Material>,
+    scene: GltfScene,
+}
+
+impl<'a> Loader<'a> {
+    fn visit(&mut self, node: &Node, parent: Transform) -> Result<(), GltfError> {
+        let columns = node.transform().matrix();
+        let mut rows = [[0.0; 4]; 4];
+        for (i, row) in rows.iter_mut().enumerate() {
+            for (j, value) in row.iter_mut().enumerate() {
+                *value = f64::from(columns[j][i]);
+            }
+        }
+        // A degenerate scale would flatten the subtree, cameras included.
+        let local = Transform::new(rows).ok_or(GltfError::DegenerateNode(node.index()))?;
+        let transform = parent * local;
+
+        if let Some(mesh) = node.mesh() {
+            if let Some(object) = self.mesh(&mesh)? {
+                self.scene.world.add(Instance::new(object, transform));
+            }
+        }
+
+        if let Some(camera) = node.camera() {
+            if let Projection::Perspective(perspective) = camera.projection() {
+                let aspect_ratio = perspective
+                    .aspect_ratio()
+                    .map_or(self.aspect_ratio, f64::from);
+                self.scene.cameras.push(Camera::new(
+                    transform.point(point!(0, 0, 0)),
+                    transform.point(point!(0, 0, -1)),
+                    transform.vector(v3!(0, 1, 0)),
+                    f64::from(perspective.yfov()).to_degrees(),
+                    aspect_ratio,
+                    0.0,
+                    1.0,
+                ));
+            }
+        }
+
+        for child in node.children() {
+            self.visit(&child, transform)?;
+        }
+        Ok(())
+    }
+
+    // Meshes are built once in object space and shared by every node using them.
+    fn mesh(&mut self, mesh: &::gltf::Mesh) -> Result<Option<Arc<dyn Hit>>, GltfError> {
+        if let Some(object) = self.meshes.get(&mesh.index()) {
+            return Ok(object.clone());
+        }
+
+        let mut primitives = HitList::new();
+        for primitive in mesh.primitives() {
+            if primitive.mode() != Mode::Triangles {
+                continue;
+            }
+            let reader = primitive.reader(|b| Some(&self.buffers[b.index()]));
+            let positions = match reader.read_positions() {
+                Some(positions) => positions
+                    .map(|[x, y, z]| point!(x, y, z))
+                    .collect::<Vec<_>>(),
+                None => continue,
+            };
+            let normals = reader
+                .read_normals()
+                .map(|normals| normals.map(|[x, y, z]| v3!(x, y, z)).collect::<Vec<_>>())
+                .unwrap_or_default();
+            let uvs: Vec<_> = reader
+                .read_tex_coords(0)
+                .map(|uvs| {
+                    // glTF puts v = 0 at the top of the image, image
+                    // textures at the bottom.
+                    uvs.into_f32()
+                        .map(|[u, v]| (f64::from(u), 1.0 - f64::from(v)))
+                        .collect()
+                })
+                .unwrap_or_default();
+            let flat = match reader.read_indices() {
+                Some(indices) => indices.into_u32().map(|i| i as usize).collect(),
+                None => (0..positions.len()).collect::<Vec<_>>(),
+            };
+            let invalid = |message: String| GltfError::Primitive {
+                mesh: mesh.index(),
+                primitive: primitive.index(),
+                message,
+            };
+            if flat.len() % 3 != 0 {
+                return Err(invalid(format!(
+                    "{} indices do not make triangles",
+                    flat.len()
+                )));
+            }
+            if let Some(index) = flat.iter().find(|&&i| i >= positions.len()) {
+                return Err(invalid(format!(
+                    "index {} out of range of {} positions",
+                    index,
+                    positions.len()
+                )));
+            }
+            for (attribute, count) in [("normals", normals.len()), ("uvs", uvs.len())] {
+                if count != 0 && count != positions.len() {
+                    return Err(invalid(format!(
+                        "{} {} for {} positions",
+                        count,
+                        attribute,
+                        positions.len()
+                    )));
+                }
+            }
+            let indices = flat
+                .chunks_exact(3)
+                .map(|f| [f[0], f[1], f[2]])
+                .collect::<Vec<_>>();
+            if indices.is_empty() {
+                continue;
+            }
+
+            let material = self.material(primitive.material());
+            let data = MeshData {
+                positions,
+                normals,
+                uvs,
+                indices,
+                material_ids: Vec::new(),
+            };
+            primitives.add(TriangleMesh::new(data, material)?);
+        }
+
+        let object: Option<Arc<dyn Hit>> = match primitives.len() {
+            0 => None,
+            1 => primitives.into_objects().pop().map(Arc::from),
+            _ => Some(Arc::new(primitives)),
+        };
+        self.meshes.insert(mesh.index(), object.clone());
+        Ok(object)
+    }
+
+    fn material(&mut self, material: ::gltf::Material) -> Arc<Material> {
+        let index = match material.index() {
+            Some(index) => index,
+            None => return self.default_material.clone(),
+        };
+        if let Some(material) = self.materials.get(&index) {
+            return material.clone();
+        }
+
+        let material = match self.document.materials().nth(index) {
+            Some(material) => self.convert(&material),
+            None => self.default_material.clone(),
+        };
+        self.materials.insert(index, material.clone());
+        material
+    }
+
+    fn convert(&mut self, material: &::gltf::Material) -> Arc<Material> {
+        let pbr = material.pbr_metallic_roughness();
+        let [r, g, b, _] = pbr.base_color_factor();
+        let factor = color!(r, g, b);
+        let base_color = self.scaled(pbr.base_color_texture(), true, factor);
+        // Roughness is in the green channel and metalness in the blue.
+        let metallic_roughness = self.texture(pbr.metallic_roughness_texture(), false);
+        let scalar = |channel: usize, factor: f32| -> Arc<dyn Texture> {
+            let factor = color!(factor);
+            match &metallic_roughness {
+                Some(texture) => {
+                    ScaledTexture::new(ChannelTexture::new(texture.clone(), channel), factor)
+                }
+                None => SolidColor::new(factor),
+            }
+        };
+        let transmission = material
+            .transmission()
+            .map_or(0.0, |t| t.transmission_factor());
+
+        let mut principled = Principled::new_texture(base_color)
+            .with_metallic_texture(scalar(2, pbr.metallic_factor()))
+            .with_roughness_texture(scalar(1, pbr.roughness_factor()))
+            .with_transmission(f64::from(transmission))
+            .with_refraction_index(material.ior().map_or(1.5, f64::from));
+
+        let [r, g, b] = material.emissive_factor();
+        let strength = material.emissive_strength().unwrap_or(1.0);
+        let emission = color!(r, g, b) * f64::from(strength);
+        if !emission.is_near_zero() {
+            let emission = self.scaled(material.emissive_texture(), true, emission);
+            principled = principled.with_emission_texture(emission);
+        }
+        Material::new_principled(principled)
+    }
+
+    // A texture times `factor`, or just `factor` without one.
+    fn scaled(
+        &mut self,
+        info: Option<texture::Info>,
+        srgb: bool,
+        factor: Color,
+    ) -> Arc<dyn Texture> {
+        match self.texture(info, srgb) {
+            Some(texture) => ScaledTexture::new(texture, factor),
+            None => SolidColor::new(factor),
+        }
+    }
+
+    // Only textures on the first set of texture coordinates can be looked up.
+    fn texture(&mut self, info: Option<texture::Info>, srgb: bool) -> Option<Arc<dyn Texture>> {
+        let info = info.filter(|info| info.tex_coord() == 0)?;
+        let texture = info.texture();
+        let index = texture.source().index();
+        let images = self.images;
+        let image = self
+            .textures
+            .entry((index, srgb))
+            .or_insert_with(|| {
+                images
+                    .get(index)
+                    .and_then(|data| decode(data, srgb))
+                    .map(Arc::new)
+            })
+            .clone()?;
+        let sampler = texture.sampler();
+        Some(ImageTexture::with_wrap(
+            image,
+            edge(sampler.wrap_s()),
+            edge(sampler.wrap_t()),
+        ))
+    }
+}
+
+fn edge(mode: WrappingMode) -> Edge {
+    match mode {
+        WrappingMode::ClampToEdge => Edge::Clamp,
+        WrappingMode::MirroredRepeat => Edge::Mirror,
+        WrappingMode::Repeat => Edge::Repeat,
+    }
+}
+
+// Converts decoded pixels to linear colour, dropping alpha and spreading
+// grey to every channel.
+fn decode(data: &image::Data, srgb: bool) -> Option<Image> {
+    let (channels, size) = match data.format {
+        Format::R8 => (1, 1),
+        Format::R8G8 => (2, 1),
+        Format::R8G8B8 => (3, 1),
+        Format::R8G8B8A8 => (4, 1),
+        Format::R16 => (1, 2),
+        Format::R16G16 => (2, 2),
+        Format::R16G16B16 => (3, 2),
+        Format::R16G16B16A16 => (4, 2),
+        Format::R32G32B32FLOAT => (3, 4),
+        Format::R32G32B32A32FLOAT => (4, 4),
+    };
+    let (width, height) = (data.width as usize, data.height as usize);
+    if width == 0 || height == 0 || data.pixels.len() != width * height * channels * size {
+        return None;
+    }
+
+    let channel = |bytes: &[u8]| match *bytes {
+        [a] => f64::from(a) / 255.0,
+        [a, b] => f64::from(u16::from_ne_bytes([a, b])) / 65535.0,
+        [a, b, c, d] => f64::from(f32::from_ne_bytes([a, b, c, d])),
+        _ => unreachable!(),
+    };
+    let linear = |value: f64| {
+        if !srgb {
+            value
+        } else if value <= 0.04045 {
+            value / 12.92
+        } else {
+            ((value + 0.055) / 1.055).powf(2.4)
+        }
+    };
+    let pixels = data
+        .pixels
+        .chunks_exact(channels * size)
+        .map(|pixel| {
+            let value = |i: usize| linear(channel(&pixel[i * size..(i + 1) * size]));
+            if channels < 3 {
+                color!(value(0))
+            } else {
+                color!(value(0), value(1), value(2))
+            }
+        })
+        .collect::<Vec<Color>>();
+    Image::new(width, height, pixels).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, path::PathBuf};
+
+    use super::*;
+    use crate::ray::Ray;
+
+    // A unit quad in the xy plane, its positions and `indices` in an
+    // external buffer, placed by the single node described by `node`.
+    fn quad(name: &str, indices: &[u16], node: &str, material: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("raytracing-rs-gltf-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+
+        let mut buffer = Vec::new();
+        for [x, y] in [[0.0f32, 0.0], [1.0, 0.0], [1.0, 1.0], [0.0, 1.0]] {
+            for c in [x, y, 0.0] {
+                buffer.extend(c.to_le_bytes());
+            }
+        }
+        for i in indices {
+            buffer.extend(i.to_le_bytes());
+        }
+        fs::write(dir.join(format!("{}.bin", name)), &buffer).unwrap();
+
+        let json = format!(
+            r#"{{
+                "asset": {{"version": "2.0"}},
+                "extensionsUsed": ["KHR_materials_emissive_strength"],
+                "scene": 0,
+                "scenes": [{{"nodes": [0]}}],
+                "nodes": [{node}],
+                "meshes": [{{"primitives": [{{
+                    "attributes": {{"POSITION": 0}}, "indices": 1, "material": 0
+                }}]}}],
+                "materials": [{material}],
+                "buffers": [{{"byteLength": {length}, "uri": "{name}.bin"}}],
+                "bufferViews": [
+                    {{"buffer": 0, "byteOffset": 0, "byteLength": 48}},
+                    {{"buffer": 0, "byteOffset": 48, "byteLength": {index_bytes}}}
+                ],
+                "accessors": [
+                    {{"bufferView": 0, "componentType": 5126, "count": 4, "type": "VEC3",
+                      "min": [0, 0, 0], "max": [1, 1, 0]}},
+                    {{"bufferView": 1, "componentType": 5123, "count": {count},
+                      "type": "SCALAR"}}
+                ]
+            }}"#,
+            node = node,
+            material = material,
+            length = buffer.len(),
+            name = name,
+            index_bytes = 2 * indices.len(),
+            count = indices.len(),
+        );
+        let path = dir.join(format!("{}.gltf", name));
+        fs::write(&path, json).unwrap();
+        path
+    }
+
+    fn load(path: PathBuf) -> Result<GltfScene, GltfError> {
+        GltfScene::load(path, 1.0)
+    }
+
+    const QUAD: [u16; 6] = [0, 1, 2, 0, 2, 3];
+
+    #[test]
+    fn loads_nodes_and_emission() {
+        let path = quad(
+            "emissive",
+            &QUAD,
+            r#"{"mesh": 0, "translation": [0, 0, -2]}"#,
+            r#"{"emissiveFactor": [1, 0.5, 0.25], "extensions":
+                {"KHR_materials_emissive_strength": {"emissiveStrength": 4}}}"#,
+        );
+        let scene = load(path).unwrap();
+        let ray = Ray::new(point!(0.5, 0.5, 1), v3!(0, 0, -1));
+        let rec = scene.world.hit(&ray, 0.001, f64::INFINITY).unwrap();
+        assert!((rec.t - 3.0).abs() < 1e-9);
+        let emitted = rec.material.emitted(&rec);
+        assert!((emitted - color!(4, 2, 1)).len() < 1e-6, "{:?}", emitted);
+    }
+
+    #[test]
+    fn rejects_malformed_primitives() {
+        let primitive = |result: Result<GltfScene, GltfError>| match result {
+            Err(GltfError::Primitive {
+                mesh, primitive, ..
+            }) => (mesh, primitive),
+            Err(err) => panic!("expected a primitive error, got {}", err),
+            Ok(_) => panic!("expected a primitive error"),
+        };
+        let node = r#"{"mesh": 0}"#;
+        let path = quad("range", &[0, 1, 4, 0, 2, 3], node, "{}");
+        assert_eq!(primitive(load(path)), (0, 0));
+        let path = quad("partial", &[0, 1, 2, 0], node, "{}");
+        assert_eq!(primitive(load(path)), (0, 0));
+    }
+
+    #[test]
+    fn rejects_degenerate_nodes() {
+        let path = quad("flat", &QUAD, r#"{"mesh": 0, "scale": [1, 0, 1]}"#, "{}");
+        assert!(matches!(load(path), Err(GltfError::DegenerateNode(0))));
+    }
+
+    #[test]
+    fn rejects_malformed_documents() {
+        let dir = quad("document", &QUAD, r#"{"mesh": 0}"#, "{}");
+        let dir = dir.parent().unwrap();
+
+        let path = dir.join("syntax.gltf");
+        fs::write(&path, r#"{"asset": {"version": "2.0"}"#).unwrap();
+        assert!(matches!(load(path), Err(GltfError::Import(_))));
+
+        let path = dir.join("empty.gltf");
+        fs::write(&path, r#"{"asset": {"version": "2.0"}}"#).unwrap();
+        assert!(matches!(load(path), Err(GltfError::NoScene)));
+
+        // The buffer is shorter than its declared length.
+        let path = dir.join("short.gltf");
+        let json = fs::read_to_string(dir.join("document.gltf")).unwrap();
+        fs::write(
+            &path,
+            json.replace("\"byteLength\": 60", "\"byteLength\": 600"),
+        )
+        .unwrap();
+        assert!(matches!(load(path), Err(GltfError::Import(_))));
+
+        assert!(matches!(
+            load(dir.join("missing.gltf")),
+            Err(GltfError::Import(_))
+        ));
+    }
+}
@@ -1,10 +1,18 @@
+use std::sync::Arc;
+
 use crate::{
+    aabb::Aabb,
+    box_shape::BoxShape,
     color,
+    instance::Instance,
     material::Material,
     point,
     random::Random,
     ray::Ray,
+    rect::{XYRect, XZRect, YZRect},
     sphere::Sphere,
+    transform::Transform,
+    v3,
     vec3::{Color, Point, Vec3},
 };
 
@@ -14,6 +22,9 @@ pub struct HitRecord<'a> {
     pub normal: Vec3,
     pub material: &'a Material,
     pub is_front_face: bool,
+    pub u: f64,
+    pub v: f64,
+    pub barycentric: Vec3,
 }
 
 impl<'a> HitRecord<'a> {
@@ -36,14 +47,136 @@ impl<'a> HitRecord<'a> {
             normal,
             material,
             is_front_face,
+            u: 0.0,
+            v: 0.0,
+            barycentric: Vec3::default(),
         }
     }
 }
 
 pub trait Hit: Send + Sync {
-    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord>;
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>>;
+
+    fn bounding_box(&self) -> Option<Aabb>;
+
+    // Solid angle density of `random_direction` from `origin`, for shapes
+    // that can be sampled as lights.
+    fn pdf_value(&self, _origin: &Point, _direction: &Vec3, _time: f64) -> f64 {
+        0.0
+    }
+
+    // `None` for shapes that cannot be sampled.
+    fn random_direction(&self, _origin: &Point, _time: f64) -> Option<Vec3> {
+        None
+    }
+
+    // Surface area, or zero where unknown; only used to estimate light power.
+    fn area(&self) -> f64 {
+        0.0
+    }
+}
+
+impl<T: Hit + ?Sized> Hit for Box<T> {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        (**self).hit(ray, t_min, t_max)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        (**self).bounding_box()
+    }
+
+    fn pdf_value(&self, origin: &Point, direction: &Vec3, time: f64) -> f64 {
+        (**self).pdf_value(origin, direction, time)
+    }
+
+    fn random_direction(&self, origin: &Point, time: f64) -> Option<Vec3> {
+        (**self).random_direction(origin, time)
+    }
+
+    fn area(&self) -> f64 {
+        (**self).area()
+    }
+}
+
+impl<T: Hit + ?Sized> Hit for Arc<T> {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        (**self).hit(ray, t_min, t_max)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        (**self).bounding_box()
+    }
+
+    fn pdf_value(&self, origin: &Point, direction: &Vec3, time: f64) -> f64 {
+        (**self).pdf_value(origin, direction, time)
+    }
+
+    fn random_direction(&self, origin: &Point, time: f64) -> Option<Vec3> {
+        (**self).random_direction(origin, time)
+    }
+
+    fn area(&self) -> f64 {
+        (**self).area()
+    }
+}
+
+impl<T: Hit + ?Sized> Hit for &T {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        (**self).hit(ray, t_min, t_max)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        (**self).bounding_box()
+    }
+
+    fn pdf_value(&self, origin: &Point, direction: &Vec3, time: f64) -> f64 {
+        (**self).pdf_value(origin, direction, time)
+    }
+
+    fn random_direction(&self, origin: &Point, time: f64) -> Option<Vec3> {
+        (**self).random_direction(origin, time)
+    }
+
+    fn area(&self) -> f64 {
+        (**self).area()
+    }
 }
 
+pub struct FlipFace {
+    object: Box<dyn Hit>,
+}
+
+impl FlipFace {
+    pub fn new(object: Box<dyn Hit>) -> Box<Self> {
+        Box::new(Self { object })
+    }
+}
+
+impl Hit for FlipFace {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        let mut rec = self.object.hit(ray, t_min, t_max)?;
+        rec.is_front_face = !rec.is_front_face;
+        Some(rec)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        self.object.bounding_box()
+    }
+
+    fn pdf_value(&self, origin: &Point, direction: &Vec3, time: f64) -> f64 {
+        self.object.pdf_value(origin, direction, time)
+    }
+
+    fn random_direction(&self, origin: &Point, time: f64) -> Option<Vec3> {
+        self.object.random_direction(origin, time)
+    }
+
+    fn area(&self) -> f64 {
+        self.object.area()
+    }
+}
+
+#[derive(Default)]
 pub struct HitList {
     objects: Vec<Box<dyn Hit>>,
 }
@@ -59,6 +192,18 @@ impl HitList {
         self.objects.push(object);
     }
 
+    pub fn len(&self) -> usize {
+        self.objects.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.objects.is_empty()
+    }
+
+    pub fn into_objects(self) -> Vec<Box<dyn Hit>> {
+        self.objects
+    }
+
     pub fn random_scene() -> Self {
         let mut scene = Self::new();
 
@@ -103,6 +248,49 @@ impl HitList {
         scene
     }
 
+    pub fn cornell_box() -> Self {
+        let mut scene = Self::new();
+
+        let red = Material::new_lambertian(color!(0.65, 0.05, 0.05));
+        let white = Material::new_lambertian(color!(0.73, 0.73, 0.73));
+        let green = Material::new_lambertian(color!(0.12, 0.45, 0.15));
+        let light = Material::new_diffuse_light(color!(15, 15, 15));
+
+        scene.add(YZRect::new(0.0, 555.0, 0.0, 555.0, 555.0, green));
+        scene.add(YZRect::new(0.0, 555.0, 0.0, 555.0, 0.0, red));
+        scene.add(FlipFace::new(XZRect::new(
+            213.0, 343.0, 227.0, 332.0, 554.0, light,
+        )));
+        scene.add(XZRect::new(0.0, 555.0, 0.0, 555.0, 0.0, white.clone()));
+        scene.add(XZRect::new(0.0, 555.0, 0.0, 555.0, 555.0, white.clone()));
+        scene.add(XYRect::new(0.0, 555.0, 0.0, 555.0, 555.0, white.clone()));
+
+        let tall: Arc<dyn Hit> = Arc::from(BoxShape::new(
+            point!(0, 0, 0),
+            point!(165, 330, 165),
+            white.clone(),
+        ) as Box<dyn Hit>);
+        let transform = Transform::translate(v3!(265, 0, 295)) * Transform::rotate_y(15.0);
+        scene.add(Instance::new(tall, transform));
+
+        let short: Arc<dyn Hit> =
+            Arc::from(BoxShape::new(point!(0, 0, 0), point!(165, 165, 165), white) as Box<dyn Hit>);
+        let transform = Transform::translate(v3!(130, 0, 65)) * Transform::rotate_y(-18.0);
+        scene.add(Instance::new(short, transform));
+
+        scene
+    }
+
+    // A copy of the `cornell_box` ceiling light, to be sampled directly.
+    pub fn cornell_box_lights() -> Self {
+        let mut lights = Self::new();
+        let light = Material::new_diffuse_light(color!(15, 15, 15));
+        lights.add(FlipFace::new(XZRect::new(
+            213.0, 343.0, 227.0, 332.0, 554.0, light,
+        )));
+        lights
+    }
+
     pub fn simple_scene() -> Self {
         let mut scene = Self::new();
 
@@ -123,8 +311,14 @@ impl HitList {
     }
 }
 
+impl From<Vec<Box<dyn Hit>>> for HitList {
+    fn from(objects: Vec<Box<dyn Hit>>) -> Self {
+        Self { objects }
+    }
+}
+
 impl Hit for HitList {
-    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
         let mut res = None;
         let mut closest_so_far = t_max;
 
@@ -137,4 +331,38 @@ impl Hit for HitList {
 
         res
     }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let mut objects = self.objects.iter();
+        let first = objects.next()?.bounding_box()?;
+        objects.try_fold(first, |bbox, object| {
+            object.bounding_box().map(|b| bbox.surrounding(&b))
+        })
+    }
+
+    // Each object is picked with equal probability.
+    fn pdf_value(&self, origin: &Point, direction: &Vec3, time: f64) -> f64 {
+        if self.objects.is_empty() {
+            return 0.0;
+        }
+        let sum = self
+            .objects
+            .iter()
+            .map(|object| object.pdf_value(origin, direction, time))
+            .sum::<f64>();
+        sum / self.objects.len() as f64
+    }
+
+    fn random_direction(&self, origin: &Point, time: f64) -> Option<Vec3> {
+        let n = self.objects.len();
+        if n == 0 {
+            return None;
+        }
+        let index = ((f64::random() * n as f64) as usize).min(n - 1);
+        self.objects[index].random_direction(origin, time)
+    }
+
+    fn area(&self) -> f64 {
+        self.objects.iter().map(|object| object.area()).sum()
+    }
 }
@@ -0,0 +1,648 @@
+use std::{fs, io, path::Path};
+
+use crate::{
+    color,
+    vec3::{clamp, Color},
+};
+
+// Linear floating point RGB image, stored top row first.
+pub struct Image {
+    width: usize,
+    height: usize,
+    data: Vec<Color>,
+}
+
+impl Image {
+    pub fn new(width: usize, height: usize, data: Vec<Color>) -> io::Result<Self> {
+        if width == 0 || height == 0 {
+            return Err(invalid("empty image"));
+        }
+        if data.len() != width * height {
+            return Err(invalid("image size mismatch"));
+        }
+        Ok(Self {
+            width,
+            height,
+            data,
+        })
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn pixel(&self, x: usize, y: usize) -> Color {
+        self.data[y * self.width + x]
+    }
+
+    // Bilinear lookup with `s` and `t` in [0, 1], `t` measured from the top row.
+    pub fn bilinear(&self, s: f64, t: f64, wrap: Wrap) -> Color {
+        match wrap {
+            Wrap::Clamp => self.bilinear_edges(s, t, Edge::Clamp, Edge::Clamp),
+            Wrap::Horizontal => self.bilinear_edges(s, t, Edge::Repeat, Edge::Clamp),
+        }
+    }
+
+    // Like `bilinear`, choosing what lies beyond the edges for columns and
+    // rows separately.
+    pub fn bilinear_edges(&self, s: f64, t: f64, columns: Edge, rows: Edge) -> Color {
+        let x = s * self.width as f64 - 0.5;
+        let y = t * self.height as f64 - 0.5;
+        let (x0, y0) = (x.floor(), y.floor());
+        let (dx, dy) = (x - x0, y - y0);
+
+        let (x1, x0) = (
+            columns.index(x0 + 1.0, self.width),
+            columns.index(x0, self.width),
+        );
+        let (y1, y0) = (
+            rows.index(y0 + 1.0, self.height),
+            rows.index(y0, self.height),
+        );
+
+        (1.0 - dy) * ((1.0 - dx) * self.pixel(x0, y0) + dx * self.pixel(x1, y0))
+            + dy * ((1.0 - dx) * self.pixel(x0, y1) + dx * self.pixel(x1, y1))
+    }
+
+    // Picks the reader from the file extension, falling back to ppm.
+    pub fn read<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let extension = path
+            .as_ref()
+            .extension()
+            .and_then(|e| e.to_str())
+            .map(|e| e.to_ascii_lowercase());
+        match extension.as_deref() {
+            Some("hdr") | Some("pic") => Self::read_hdr(path),
+            Some("pfm") => Self::read_pfm(path),
+            _ => Self::read_ppm(path),
+        }
+    }
+
+    // Reads P3 or P6 files, undoing the gamma 2 applied by `Vec3::to_rgb_string`.
+    pub fn read_ppm<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let bytes = fs::read(path)?;
+        let (header, offset) = header_tokens(&bytes, 4)?;
+
+        let parse = |s: &str| {
+            s.parse::<usize>()
+                .map_err(|_| invalid("invalid ppm header"))
+        };
+        let width = parse(&header[1])?;
+        let height = parse(&header[2])?;
+        let max = parse(&header[3])? as f64;
+        if width == 0 || height == 0 {
+            return Err(invalid("invalid ppm size"));
+        }
+        if max <= 0.0 || max > 65535.0 {
+            return Err(invalid("invalid ppm maximum value"));
+        }
+
+        let count = width * height * 3;
+        let samples = match header[0].as_str() {
+            "P3" => std::str::from_utf8(&bytes[offset..])
+                .map_err(|_| invalid("invalid ppm data"))?
+                .split_ascii_whitespace()
+                .take(count)
+                .map(|s| s.parse::<f64>().map_err(|_| invalid("invalid ppm sample")))
+                .collect::<io::Result<Vec<_>>>()?,
+            "P6" => {
+                let data = &bytes[(offset + 1).min(bytes.len())..];
+                if max < 256.0 {
+                    data.iter().take(count).map(|&b| f64::from(b)).collect()
+                } else {
+                    data.chunks_exact(2)
+                        .take(count)
+                        .map(|b| f64::from(u16::from_be_bytes([b[0], b[1]])))
+                        .collect()
+                }
+            }
+            _ => return Err(invalid("unsupported ppm format")),
+        };
+        if samples.len() < count {
+            return Err(invalid("truncated ppm data"));
+        }
+
+        let data = samples
+            .chunks_exact(3)
+            .map(|rgb| {
+                let c = color!(rgb[0], rgb[1], rgb[2]) / max;
+                c * c
+            })
+            .collect();
+        Self::new(width, height, data)
+    }
+
+    // Reads Portable Float Maps, both "PF" (rgb) and "Pf" (grey).
+    pub fn read_pfm<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let bytes = fs::read(path)?;
+        let (header, offset) = header_tokens(&bytes, 4)?;
+
+        let channels = match header[0].as_str() {
+            "PF" => 3,
+            "Pf" => 1,
+            _ => return Err(invalid("unsupported pfm format")),
+        };
+        let parse = |s: &str| {
+            s.parse::<usize>()
+                .map_err(|_| invalid("invalid pfm header"))
+        };
+        let width = parse(&header[1])?;
+        let height = parse(&header[2])?;
+        let scale = header[3]
+            .parse::<f64>()
+            .map_err(|_| invalid("invalid pfm scale"))?;
+        if width == 0 || height == 0 || scale == 0.0 {
+            return Err(invalid("invalid pfm header"));
+        }
+
+        let count = width * height * channels;
+        let data = &bytes[(offset + 1).min(bytes.len())..];
+        if data.len() < count * 4 {
+            return Err(invalid("truncated pfm data"));
+        }
+        let samples = data
+            .chunks_exact(4)
+            .take(count)
+            .map(|b| {
+                let b = [b[0], b[1], b[2], b[3]];
+                // A negative scale marks little endian data.
+                f64::from(if scale < 0.0 {
+                    f32::from_le_bytes(b)
+                } else {
+                    f32::from_be_bytes(b)
+                })
+            })
+            .collect::<Vec<_>>();
+
+        // Rows are stored bottom to top.
+        let data = samples
+            .chunks_exact(width * channels)
+            .rev()
+            .flat_map(|row| row.chunks_exact(channels))
+            .map(|c| {
+                if channels == 3 {
+                    color!(c[0], c[1], c[2])
+                } else {
+                    color!(c[0])
+                }
+            })
+            .collect();
+        Self::new(width, height, data)
+    }
+
+    // Reads Radiance RGBE files, flat or run-length encoded.
+    pub fn read_hdr<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let bytes = fs::read(path)?;
+
+        let mut offset = 0;
+        let mut line = || {
+            let start = offset;
+            while offset < bytes.len() && bytes[offset] != b'\n' {
+                offset += 1;
+            }
+            let end = offset;
+            offset = (offset + 1).min(bytes.len());
+            String::from_utf8_lossy(&bytes[start..end]).into_owned()
+        };
+
+        if !line().starts_with("#?") {
+            return Err(invalid("missing radiance signature"));
+        }
+        loop {
+            let header = line();
+            if header.is_empty() {
+                break;
+            }
+            if let Some(format) = header.strip_prefix("FORMAT=") {
+                if format.trim() != "32-bit_rle_rgbe" {
+                    return Err(invalid("unsupported hdr pixel format"));
+                }
+            }
+        }
+
+        let resolution = line();
+        let tokens = resolution.split_ascii_whitespace().collect::<Vec<_>>();
+        let (flip, height, width) = match tokens.as_slice() {
+            [y, height, "+X", width] if *y == "-Y" || *y == "+Y" => {
+                let parse = |s: &str| {
+                    s.parse::<usize>()
+                        .map_err(|_| invalid("invalid hdr resolution"))
+                };
+                (*y == "+Y", parse(height)?, parse(width)?)
+            }
+            _ => return Err(invalid("unsupported hdr orientation")),
+        };
+        if width == 0 || height == 0 {
+            return Err(invalid("invalid hdr resolution"));
+        }
+
+        let mut reader = Rgbe {
+            bytes: &bytes,
+            offset,
+        };
+        let mut rows = Vec::with_capacity(height);
+        for _ in 0..height {
+            rows.push(reader.scanline(width)?);
+        }
+        if flip {
+            rows.reverse();
+        }
+
+        let data = rows
+            .iter()
+            .flatten()
+            .map(|&[r, g, b, e]| {
+                if e == 0 {
+                    color!(0)
+                } else {
+                    let f = 2f64.powi(i32::from(e) - 136);
+                    color!(f64::from(r) * f, f64::from(g) * f, f64::from(b) * f)
+                }
+            })
+            .collect();
+        Self::new(width, height, data)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Wrap {
+    Clamp,
+    // Repeats columns and clamps rows, as for equirectangular maps.
+    Horizontal,
+}
+
+// What lookups beyond the edges of an image find along one axis.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Edge {
+    Clamp,
+    Repeat,
+    // Repeats alternately flipped.
+    Mirror,
+}
+
+impl Edge {
+    // The pixel at `index` along an axis `size` pixels long.
+    fn index(self, index: f64, size: usize) -> usize {
+        let size = size as f64;
+        let index = match self {
+            Self::Clamp => clamp(index, 0.0, size - 1.0),
+            Self::Repeat => index.rem_euclid(size),
+            Self::Mirror => {
+                let index = index.rem_euclid(2.0 * size);
+                if index < size {
+                    index
+                } else {
+                    2.0 * size - 1.0 - index
+                }
+            }
+        };
+        (index as usize).min(size as usize - 1)
+    }
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+// Collects `count` whitespace separated tokens, skipping comments, and
+// returns them with the offset just past the last one.
+fn header_tokens(bytes: &[u8], count: usize) -> io::Result<(Vec<String>, usize)> {
+    let mut offset = 0;
+    let mut header = Vec::new();
+    while header.len() < count {
+        while offset < bytes.len() && bytes[offset].is_ascii_whitespace() {
+            offset += 1;
+        }
+        if offset < bytes.len() && bytes[offset] == b'#' {
+            while offset < bytes.len() && bytes[offset] != b'\n' {
+                offset += 1;
+            }
+            continue;
+        }
+        let start = offset;
+        while offset < bytes.len() && !bytes[offset].is_ascii_whitespace() {
+            offset += 1;
+        }
+        if start == offset {
+            return Err(invalid("truncated image header"));
+        }
+        header.push(String::from_utf8_lossy(&bytes[start..offset]).into_owned());
+    }
+    Ok((header, offset))
+}
+
+struct Rgbe<'a> {
+    bytes: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> Rgbe<'a> {
+    fn byte(&mut self) -> io::Result<u8> {
+        let byte = *self
+            .bytes
+            .get(self.offset)
+            .ok_or_else(|| invalid("truncated hdr data"))?;
+        self.offset += 1;
+        Ok(byte)
+    }
+
+    fn pixel(&mut self) -> io::Result<[u8; 4]> {
+        Ok([self.byte()?, self.byte()?, self.byte()?, self.byte()?])
+    }
+
+    fn scanline(&mut self, width: usize) -> io::Result<Vec<[u8; 4]>> {
+        if !(8..=0x7fff).contains(&width) {
+            return self.flat(width);
+        }
+        let start = self.offset;
+        let header = self.pixel()?;
+        if header[0] != 2 || header[1] != 2 || header[2] & 0x80 != 0 {
+            self.offset = start;
+            return self.flat(width);
+        }
+        if (usize::from(header[2]) << 8 | usize::from(header[3])) != width {
+            return Err(invalid("hdr scanline width mismatch"));
+        }
+
+        // Each channel is run-length encoded separately.
+        let mut row = vec![[0; 4]; width];
+        for channel in 0..4 {
+            let mut x = 0;
+            while x < width {
+                let count = self.byte()?;
+                let (run, count) = if count > 128 {
+                    (true, usize::from(count - 128))
+                } else {
+                    (false, usize::from(count))
+                };
+                if count == 0 || x + count > width {
+                    return Err(invalid("bad hdr run length"));
+                }
+                if run {
+                    let value = self.byte()?;
+                    for pixel in &mut row[x..x + count] {
+                        pixel[channel] = value;
+                    }
+                } else {
+                    for pixel in &mut row[x..x + count] {
+                        pixel[channel] = self.byte()?;
+                    }
+                }
+                x += count;
+            }
+        }
+        Ok(row)
+    }
+
+    // Uncompressed pixels, also handling the old (1, 1, 1, n) repeat runs.
+    fn flat(&mut self, width: usize) -> io::Result<Vec<[u8; 4]>> {
+        let mut row: Vec<[u8; 4]> = Vec::with_capacity(width);
+        let mut shift = 0;
+        while row.len() < width {
+            let pixel = self.pixel()?;
+            if pixel[..3] == [1, 1, 1] {
+                let previous = *row.last().ok_or_else(|| invalid("bad hdr run length"))?;
+                let count = usize::from(pixel[3]) << shift;
+                if row.len() + count > width {
+                    return Err(invalid("bad hdr run length"));
+                }
+                row.resize(row.len() + count, previous);
+                shift += 8;
+            } else {
+                row.push(pixel);
+                shift = 0;
+            }
+        }
+        Ok(row)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn write(name: &str, bytes: &[u8]) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("raytracing-rs-image-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(name);
+        fs::write(&path, bytes).unwrap();
+        path
+    }
+
+    fn pixels(image: &Image) -> Vec<[f64; 3]> {
+        (0..image.height())
+            .flat_map(|y| (0..image.width()).map(move |x| (x, y)))
+            .map(|(x, y)| {
+                let c = image.pixel(x, y);
+                [c.x, c.y, c.z]
+            })
+            .collect()
+    }
+
+    // With an exponent of 136 each mantissa byte reads back as itself.
+    fn rgbe(value: u8) -> [u8; 4] {
+        [value, value / 2, 255 - value, 136]
+    }
+
+    fn expected(values: &[u8]) -> Vec<[f64; 3]> {
+        values
+            .iter()
+            .map(|&v| [f64::from(v), f64::from(v / 2), f64::from(255 - v)])
+            .collect()
+    }
+
+    // Runs of three or more equal bytes become runs, the rest literals.
+    fn encode_channel(values: &[u8], out: &mut Vec<u8>) {
+        let mut x = 0;
+        while x < values.len() {
+            let run = values[x..]
+                .iter()
+                .take(127)
+                .take_while(|&&v| v == values[x])
+                .count();
+            if run >= 3 {
+                out.extend([128 + run as u8, values[x]]);
+                x += run;
+                continue;
+            }
+            let mut end = x;
+            while end < values.len()
+                && end - x < 128
+                && !values[end..].iter().take(3).all(|&v| v == values[end])
+            {
+                end += 1;
+            }
+            let end = end.max(x + 1).min(values.len());
+            out.push((end - x) as u8);
+            out.extend(&values[x..end]);
+            x = end;
+        }
+    }
+
+    fn hdr(resolution: &str, body: &[u8]) -> Vec<u8> {
+        let mut bytes =
+            format!("#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n{}\n", resolution).into_bytes();
+        bytes.extend(body);
+        bytes
+    }
+
+    fn rle_rows(rows: &[Vec<u8>]) -> Vec<u8> {
+        let mut body = Vec::new();
+        for row in rows {
+            let width = row.len();
+            body.extend([2, 2, (width >> 8) as u8, width as u8]);
+            let pixels: Vec<_> = row.iter().map(|&v| rgbe(v)).collect();
+            for channel in 0..4 {
+                let values: Vec<_> = pixels.iter().map(|p| p[channel]).collect();
+                encode_channel(&values, &mut body);
+            }
+        }
+        body
+    }
+
+    #[test]
+    fn hdr_rle_round_trips() {
+        let rows = vec![
+            vec![10, 10, 10, 10, 20, 30, 40, 40, 40, 50, 60, 60],
+            (0..12).map(|x| x * 20).collect::<Vec<_>>(),
+        ];
+        let path = write("rle.hdr", &hdr("-Y 2 +X 12", &rle_rows(&rows)));
+        let image = Image::read(path).unwrap();
+        assert_eq!((image.width(), image.height()), (12, 2));
+        assert_eq!(pixels(&image), expected(&rows.concat()));
+
+        // Bottom-up files are flipped to top row first.
+        let path = write("flipped.hdr", &hdr("+Y 2 +X 12", &rle_rows(&rows)));
+        let flipped: Vec<_> = rows.iter().rev().flatten().copied().collect();
+        assert_eq!(pixels(&Image::read(path).unwrap()), expected(&flipped));
+    }
+
+    #[test]
+    fn hdr_flat_and_old_runs() {
+        let mut body = Vec::new();
+        body.extend(rgbe(5));
+        body.extend([1, 1, 1, 2]);
+        body.extend(rgbe(9));
+        let path = write("flat.hdr", &hdr("-Y 1 +X 4", &body));
+        assert_eq!(pixels(&Image::read(path).unwrap()), expected(&[5, 5, 5, 9]));
+
+        // A zero exponent is black whatever the mantissas say.
+        let path = write("black.hdr", &hdr("-Y 1 +X 1", &[200, 100, 50, 0]));
+        assert_eq!(pixels(&Image::read(path).unwrap()), [[0.0; 3]]);
+    }
+
+    #[test]
+    fn hdr_rejects_malformed_input() {
+        let rows = rle_rows(&[vec![1; 12]]);
+        let mut cases = vec![
+            (
+                "magic.hdr",
+                b"RADIANCE\n\n-Y 1 +X 1\n\x01\x01\x01\x80".to_vec(),
+            ),
+            (
+                "format.hdr",
+                b"#?RADIANCE\nFORMAT=32-bit_rle_xyze\n\n-Y 1 +X 1\n\x01\x01\x01\x80".to_vec(),
+            ),
+            ("orientation.hdr", hdr("+X 1 -Y 1", &[1, 1, 1, 128])),
+            ("empty.hdr", hdr("-Y 0 +X 1", &[])),
+            ("width.hdr", hdr("-Y 1 +X 13", &rows)),
+            ("truncated.hdr", hdr("-Y 1 +X 12", &rows[..rows.len() - 1])),
+            ("taller.hdr", hdr("-Y 2 +X 12", &rows)),
+            ("leading run.hdr", hdr("-Y 1 +X 2", &[1, 1, 1, 2])),
+            (
+                "long run.hdr",
+                hdr("-Y 1 +X 2", &[4, 4, 4, 136, 1, 1, 1, 2]),
+            ),
+        ];
+        // A run reaching past the end of the scanline.
+        let mut overrun = vec![2, 2, 0, 12, 128 + 13, 1];
+        overrun.extend(&rows[6..]);
+        cases.push(("overrun.hdr", hdr("-Y 1 +X 12", &overrun)));
+
+        for (name, bytes) in cases {
+            match Image::read(write(name, &bytes)) {
+                Err(err) => assert_eq!(err.kind(), io::ErrorKind::InvalidData, "{}", name),
+                Ok(_) => panic!("{} was accepted", name),
+            }
+        }
+    }
+
+    fn pfm(magic: &str, scale: f32, samples: &[f32]) -> Vec<u8> {
+        let mut bytes = format!("{}\n2 2\n{}\n", magic, scale).into_bytes();
+        for sample in samples {
+            bytes.extend(if scale < 0.0 {
+                sample.to_le_bytes()
+            } else {
+                sample.to_be_bytes()
+            });
+        }
+        bytes
+    }
+
+    #[test]
+    fn pfm_reads_both_endiannesses() {
+        // Rows are stored bottom to top.
+        let samples: Vec<f32> = (0..12).map(|i| i as f32 * 0.5 - 1.0).collect();
+        let top_first = |samples: &[f32], channels: usize| {
+            let mut rows: Vec<_> = samples.chunks(2 * channels).collect();
+            rows.reverse();
+            rows.concat()
+                .chunks(channels)
+                .map(|c| {
+                    let c: Vec<_> = c.iter().map(|&v| f64::from(v)).collect();
+                    if channels == 3 {
+                        [c[0], c[1], c[2]]
+                    } else {
+                        [c[0]; 3]
+                    }
+                })
+                .collect::<Vec<_>>()
+        };
+        for scale in [-1.0, 1.0] {
+            let path = write("color.pfm", &pfm("PF", scale, &samples));
+            assert_eq!(pixels(&Image::read(path).unwrap()), top_first(&samples, 3));
+            let path = write("grey.pfm", &pfm("Pf", scale, &samples[..4]));
+            assert_eq!(
+                pixels(&Image::read(path).unwrap()),
+                top_first(&samples[..4], 1)
+            );
+        }
+
+        // Read with the wrong byte order, the values come out different.
+        let mut swapped = b"PF\n2 2\n-1\n".to_vec();
+        swapped.extend(&pfm("PF", 1.0, &samples)[b"PF\n2 2\n1\n".len()..]);
+        let path = write("swapped.pfm", &swapped);
+        assert_ne!(pixels(&Image::read(path).unwrap()), top_first(&samples, 3));
+    }
+
+    #[test]
+    fn pfm_rejects_malformed_input() {
+        let samples = [0.0; 12];
+        let mut truncated = pfm("PF", -1.0, &samples);
+        truncated.pop();
+        for (name, bytes) in [
+            ("magic.pfm", pfm("P6", -1.0, &samples)),
+            ("scale.pfm", pfm("PF", 0.0, &samples)),
+            ("truncated.pfm", truncated),
+            ("header.pfm", b"PF\n2\n".to_vec()),
+            ("size.pfm", b"PF\n-2 2\n-1\n".to_vec()),
+        ] {
+            match Image::read(write(name, &bytes)) {
+                Err(err) => assert_eq!(err.kind(), io::ErrorKind::InvalidData, "{}", name),
+                Ok(_) => panic!("{} was accepted", name),
+            }
+        }
+    }
+
+    #[test]
+    fn new_checks_the_size() {
+        assert!(Image::new(0, 1, Vec::new()).is_err());
+        assert!(Image::new(2, 1, vec![color!(0)]).is_err());
+        assert!(Image::new(1, 1, vec![color!(0)]).is_ok());
+    }
+}
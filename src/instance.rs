@@ -0,0 +1,150 @@
+use std::sync::Arc;
+
+use crate::{
+    aabb::Aabb,
+    hit::{Hit, HitRecord},
+    ray::Ray,
+    transform::{AnimatedTransform, Transform},
+    vec3::{Point, Vec3},
+};
+
+pub struct Instance {
+    object: Arc<dyn Hit>,
+    transform: AnimatedTransform,
+    bbox: Option<Aabb>,
+}
+
+impl Instance {
+    pub fn new(object: Arc<dyn Hit>, transform: Transform) -> Box<Self> {
+        Self::new_animated(object, AnimatedTransform::fixed(transform))
+    }
+
+    pub fn new_animated(object: Arc<dyn Hit>, transform: AnimatedTransform) -> Box<Self> {
+        let bbox = object
+            .bounding_box()
+            .map(|bbox| transform.bounding_box(&bbox));
+        Box::new(Self {
+            object,
+            transform,
+            bbox,
+        })
+    }
+}
+
+impl Hit for Instance {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        let transform = self.transform.at(ray.time);
+        let inverse = transform.inverse();
+        let local = Ray::with_time(
+            inverse.point(ray.origin),
+            inverse.vector(ray.direction),
+            ray.time,
+        );
+        let mut rec = self.object.hit(&local, t_min, t_max)?;
+        rec.point = transform.point(rec.point);
+        rec.normal = transform.normal(rec.normal).unit();
+        Some(rec)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        self.bbox
+    }
+
+    // A linear map `m` takes the unit direction `d` to `m d`, stretching
+    // solid angles around it by |det m| / |m d|³.
+    fn pdf_value(&self, origin: &Point, direction: &Vec3, time: f64) -> f64 {
+        let inverse = self.transform.at(time).inverse();
+        let local = inverse.vector(direction.unit());
+        let stretch = inverse.determinant().abs() / local.len().powi(3);
+        self.object.pdf_value(&inverse.point(*origin), &local, time) * stretch
+    }
+
+    fn random_direction(&self, origin: &Point, time: f64) -> Option<Vec3> {
+        let transform = self.transform.at(time);
+        let local = transform.inverse().point(*origin);
+        let direction = self.object.random_direction(&local, time)?;
+        Some(transform.vector(direction))
+    }
+
+    // Other transforms stretch a surface by different amounts depending on
+    // its orientation, so the area is only known for similarities.
+    fn area(&self) -> f64 {
+        match self.transform.area_scale() {
+            Some(scale) => self.object.area() * scale,
+            None => 0.0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f64::consts::PI;
+
+    use super::*;
+    use crate::{color, material::Material, point, rect::XYRect, sphere::Sphere, v3};
+
+    // Midpoint rule over equal-area cells of the sphere of directions.
+    fn integrate_over_directions<F: Fn(Vec3) -> f64>(f: F) -> f64 {
+        let (rows, columns) = (600, 1200);
+        let mut sum = 0.0;
+        for i in 0..rows {
+            let z = 1.0 - 2.0 * (i as f64 + 0.5) / rows as f64;
+            let r = (1.0 - z * z).sqrt();
+            for j in 0..columns {
+                let phi = 2.0 * PI * (j as f64 + 0.5) / columns as f64;
+                sum += f(v3!(r * phi.cos(), r * phi.sin(), z));
+            }
+        }
+        sum * 4.0 * PI / (rows * columns) as f64
+    }
+
+    #[test]
+    fn stretched_pdf_integrates_to_one() {
+        let sphere: Arc<dyn Hit> = Arc::from(Sphere::new(
+            point!(0, 0, 0),
+            1.0,
+            Material::new_lambertian(color!(1)),
+        ) as Box<dyn Hit>);
+        let transform = Transform::translate(v3!(0, 0, -5))
+            * Transform::rotate_y(30.0)
+            * Transform::scale(v3!(1, 2, 0.5)).unwrap();
+        let ellipsoid = Instance::new(sphere, transform);
+        let origin = point!(0.3, 0.2, 0);
+        let integral = integrate_over_directions(|d| ellipsoid.pdf_value(&origin, &d, 0.0));
+        assert!((integral - 1.0).abs() < 0.01, "{}", integral);
+    }
+
+    #[test]
+    fn scaled_rect_matches_the_bigger_rect() {
+        let material = Material::new_lambertian(color!(1));
+        let rect: Arc<dyn Hit> =
+            Arc::from(XYRect::new(0.0, 1.0, 0.0, 1.0, 0.0, material.clone()) as Box<dyn Hit>);
+        let scaled = Instance::new(rect, Transform::scale(v3!(2, 3, 1)).unwrap());
+        let direct = XYRect::new(0.0, 2.0, 0.0, 3.0, 0.0, material);
+
+        let origin = point!(0.5, 1, 2);
+        for direction in [v3!(0, 0, -1), v3!(0.3, 0.6, -1), v3!(-0.2, 1.5, -2)] {
+            let expected = direct.pdf_value(&origin, &direction, 0.0);
+            assert!(expected > 0.0);
+            let actual = scaled.pdf_value(&origin, &direction, 0.0);
+            assert!(
+                (actual - expected).abs() < 1e-9 * expected,
+                "{:?}",
+                direction
+            );
+        }
+        assert_eq!(scaled.area(), 0.0);
+    }
+
+    #[test]
+    fn similar_instances_scale_the_area() {
+        let rect: Arc<dyn Hit> =
+            Arc::from(
+                XYRect::new(0.0, 1.0, 0.0, 2.0, 0.0, Material::new_lambertian(color!(1)))
+                    as Box<dyn Hit>,
+            );
+        let transform = Transform::rotate_x(40.0) * Transform::scale(v3!(3, 3, 3)).unwrap();
+        let instance = Instance::new(rect, transform);
+        assert!((instance.area() - 18.0).abs() < 1e-9);
+    }
+}
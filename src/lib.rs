@@ -1,7 +1,33 @@
+pub mod aabb;
+pub mod background;
+pub mod box_shape;
+pub mod bvh;
 pub mod camera;
+pub mod distribution;
+pub mod gltf;
 pub mod hit;
+pub mod image;
+pub mod instance;
+pub mod light;
+pub mod light_sampler;
 pub mod material;
+pub mod mesh;
+pub mod microfacet;
+pub mod moving_sphere;
+pub mod obj;
+pub mod onb;
+pub mod pdf;
+pub mod perlin;
+pub mod plane;
+pub mod ply;
+pub mod principled;
 pub mod random;
 pub mod ray;
+pub mod rect;
+pub mod scene;
+pub mod spectrum;
 pub mod sphere;
+pub mod texture;
+pub mod transform;
+pub mod triangle;
 pub mod vec3;
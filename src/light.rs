@@ -0,0 +1,313 @@
+use std::{f64::consts::PI, sync::Arc};
+
+use crate::{
+    aabb::Aabb,
+    hit::Hit,
+    onb::Onb,
+    ray::Ray,
+    v3,
+    vec3::{Color, Point, Vec3},
+};
+
+// Incident illumination from a light at a shading point. `direction` is unit
+// length and `pdf` is in solid angle, or 1 for lights with a single direction.
+pub struct LightSample {
+    pub direction: Vec3,
+    pub distance: f64,
+    pub radiance: Color,
+    pub pdf: f64,
+}
+
+pub trait Light: Send + Sync {
+    fn sample(&self, point: &Point, time: f64) -> Option<LightSample>;
+
+    // Density of `sample` picking `direction`, only meaningful for lights
+    // that rays can hit.
+    fn pdf(&self, point: &Point, direction: &Vec3, time: f64) -> f64;
+
+    // Lights rays cannot hit are only reached through `sample`.
+    fn is_hittable(&self) -> bool;
+
+    // How far a ray from `point` along `direction` travels before meeting
+    // the light, for lights rays can hit.
+    fn distance(&self, _point: &Point, _direction: &Vec3, _time: f64) -> Option<f64> {
+        None
+    }
+
+    // Rough emitted power, used to decide which lights to sample. Distant
+    // lights scale with the size of the scene.
+    fn power(&self, world_radius: f64) -> f64;
+
+    // Where the light is, or `None` for lights at infinity.
+    fn bounds(&self) -> Option<Aabb>;
+}
+
+// Emissive geometry; it must also be part of the world to be visible.
+pub struct AreaLight<H = Arc<dyn Hit>> {
+    object: H,
+}
+
+impl AreaLight {
+    pub fn new(object: Arc<dyn Hit>) -> Box<Self> {
+        Box::new(Self { object })
+    }
+}
+
+impl<'a> AreaLight<&'a dyn Hit> {
+    pub(crate) fn borrowed(object: &'a dyn Hit) -> Self {
+        Self { object }
+    }
+}
+
+impl<H: Hit> Light for AreaLight<H> {
+    fn sample(&self, point: &Point, time: f64) -> Option<LightSample> {
+        let direction = self.object.random_direction(point, time)?;
+        let ray = Ray::with_time(*point, direction, time);
+        let rec = self.object.hit(&ray, 0.001, f64::INFINITY)?;
+        Some(LightSample {
+            direction: direction.unit(),
+            distance: rec.t * direction.len(),
+            radiance: rec.material.emitted(&rec),
+            pdf: self.object.pdf_value(point, &direction, time),
+        })
+    }
+
+    fn pdf(&self, point: &Point, direction: &Vec3, time: f64) -> f64 {
+        self.object.pdf_value(point, direction, time)
+    }
+
+    fn is_hittable(&self) -> bool {
+        true
+    }
+
+    fn distance(&self, point: &Point, direction: &Vec3, time: f64) -> Option<f64> {
+        let ray = Ray::with_time(*point, *direction, time);
+        let rec = self.object.hit(&ray, 0.001, f64::INFINITY)?;
+        Some(rec.t * direction.len())
+    }
+
+    // Averages the radiance seen along fixed rays aimed at the middle of the
+    // object from all around it, so the estimate is the same on every run.
+    fn power(&self, _world_radius: f64) -> f64 {
+        let bbox = match self.object.bounding_box() {
+            Some(bbox) => bbox,
+            None => return 0.0,
+        };
+        let center = bbox.centroid();
+        let radius = (bbox.maximum - bbox.minimum).len().max(1e-4);
+        let mut radiance = 0.0;
+        let mut hits = 0;
+        for x in -1..=1 {
+            for y in -1..=1 {
+                for z in -1..=1 {
+                    if (x, y, z) == (0, 0, 0) {
+                        continue;
+                    }
+                    let direction = v3!(x, y, z).unit();
+                    let ray = Ray::new(center + radius * direction, -direction);
+                    if let Some(rec) = self.object.hit(&ray, 0.001, f64::INFINITY) {
+                        radiance += rec.material.emitted(&rec).luminance();
+                        hits += 1;
+                    }
+                }
+            }
+        }
+        if hits == 0 {
+            return 0.0;
+        }
+        PI * self.object.area() * radiance / f64::from(hits)
+    }
+
+    fn bounds(&self) -> Option<Aabb> {
+        self.object.bounding_box()
+    }
+}
+
+pub struct PointLight {
+    position: Point,
+    intensity: Color,
+}
+
+impl PointLight {
+    pub fn new(position: Point, intensity: Color) -> Box<Self> {
+        Box::new(Self {
+            position,
+            intensity,
+        })
+    }
+}
+
+impl Light for PointLight {
+    fn sample(&self, point: &Point, _time: f64) -> Option<LightSample> {
+        let offset = self.position - *point;
+        let distance_squared = offset.len_squared();
+        if distance_squared == 0.0 {
+            return None;
+        }
+        Some(LightSample {
+            direction: offset.unit(),
+            distance: distance_squared.sqrt(),
+            radiance: self.intensity / distance_squared,
+            pdf: 1.0,
+        })
+    }
+
+    fn pdf(&self, _point: &Point, _direction: &Vec3, _time: f64) -> f64 {
+        0.0
+    }
+
+    fn is_hittable(&self) -> bool {
+        false
+    }
+
+    fn power(&self, _world_radius: f64) -> f64 {
+        4.0 * PI * self.intensity.luminance()
+    }
+
+    fn bounds(&self) -> Option<Aabb> {
+        Some(Aabb::new(self.position, self.position))
+    }
+}
+
+pub struct SpotLight {
+    position: Point,
+    direction: Vec3,
+    intensity: Color,
+    cos_cone: f64,
+    cos_falloff: f64,
+}
+
+impl SpotLight {
+    // Full intensity within `falloff_start` degrees of the axis, fading out
+    // smoothly to nothing at `cone_angle` degrees.
+    pub fn new(
+        position: Point,
+        target: Point,
+        intensity: Color,
+        cone_angle: f64,
+        falloff_start: f64,
+    ) -> Box<Self> {
+        let cos_cone = cone_angle.to_radians().cos();
+        Box::new(Self {
+            position,
+            direction: (target - position).unit(),
+            intensity,
+            cos_cone,
+            cos_falloff: falloff_start.to_radians().cos().max(cos_cone),
+        })
+    }
+
+    fn falloff(&self, cos_theta: f64) -> f64 {
+        if cos_theta <= self.cos_cone {
+            return 0.0;
+        }
+        if cos_theta >= self.cos_falloff {
+            return 1.0;
+        }
+        let t = (cos_theta - self.cos_cone) / (self.cos_falloff - self.cos_cone);
+        t * t * (3.0 - 2.0 * t)
+    }
+}
+
+impl Light for SpotLight {
+    fn sample(&self, point: &Point, _time: f64) -> Option<LightSample> {
+        let offset = self.position - *point;
+        let distance_squared = offset.len_squared();
+        if distance_squared == 0.0 {
+            return None;
+        }
+        let direction = offset.unit();
+        let falloff = self.falloff((-direction).dot(&self.direction));
+        if falloff == 0.0 {
+            return None;
+        }
+        Some(LightSample {
+            direction,
+            distance: distance_squared.sqrt(),
+            radiance: falloff * self.intensity / distance_squared,
+            pdf: 1.0,
+        })
+    }
+
+    fn pdf(&self, _point: &Point, _direction: &Vec3, _time: f64) -> f64 {
+        0.0
+    }
+
+    fn is_hittable(&self) -> bool {
+        false
+    }
+
+    fn power(&self, _world_radius: f64) -> f64 {
+        let cone = 1.0 - 0.5 * (self.cos_cone + self.cos_falloff);
+        2.0 * PI * cone * self.intensity.luminance()
+    }
+
+    fn bounds(&self) -> Option<Aabb> {
+        Some(Aabb::new(self.position, self.position))
+    }
+}
+
+// A distant light such as the sun. With a nonzero angular diameter it is a
+// disk of directions, giving soft shadows while delivering the same
+// `irradiance` to surfaces facing it.
+pub struct DirectionalLight {
+    direction: Vec3,
+    irradiance: Color,
+    cos_max: f64,
+}
+
+impl DirectionalLight {
+    // `direction` is the way the light travels.
+    pub fn new(direction: Vec3, irradiance: Color, angular_diameter: f64) -> Box<Self> {
+        Box::new(Self {
+            direction: direction.unit(),
+            irradiance,
+            cos_max: (angular_diameter / 2.0).to_radians().cos(),
+        })
+    }
+}
+
+impl Light for DirectionalLight {
+    fn sample(&self, _point: &Point, _time: f64) -> Option<LightSample> {
+        let to_light = -self.direction;
+        if self.cos_max >= 1.0 {
+            return Some(LightSample {
+                direction: to_light,
+                distance: f64::INFINITY,
+                radiance: self.irradiance,
+                pdf: 1.0,
+            });
+        }
+
+        let solid_angle = 2.0 * PI * (1.0 - self.cos_max);
+        let sin_max = (1.0 - self.cos_max * self.cos_max).sqrt();
+        let local = Vec3::random_to_sphere(sin_max, 1.0);
+        Some(LightSample {
+            direction: Onb::new(&to_light).local(&local).unit(),
+            distance: f64::INFINITY,
+            radiance: self.irradiance / solid_angle,
+            pdf: 1.0 / solid_angle,
+        })
+    }
+
+    fn pdf(&self, _point: &Point, _direction: &Vec3, _time: f64) -> f64 {
+        0.0
+    }
+
+    fn is_hittable(&self) -> bool {
+        false
+    }
+
+    fn power(&self, world_radius: f64) -> f64 {
+        PI * world_radius * world_radius * self.irradiance.luminance()
+    }
+
+    fn bounds(&self) -> Option<Aabb> {
+        None
+    }
+}
+
+// Whether nothing in `world` blocks `ray` before `distance`.
+pub fn visible(world: &dyn Hit, ray: &Ray, distance: f64) -> bool {
+    world.hit(ray, 0.001, distance - 0.001).is_none()
+}
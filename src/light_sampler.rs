@@ -0,0 +1,322 @@
+use std::cmp::Ordering;
+
+use crate::{
+    aabb::Aabb,
+    bvh::{self, BvhBuilder},
+    distribution::Distribution1D,
+    random::Random,
+    v3,
+    vec3::Point,
+};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum LightSampling {
+    Uniform,
+    // Proportional to each light's power.
+    #[default]
+    Power,
+    // Proportional to power over distance squared, estimated per shading
+    // point with a tree of light bounds.
+    Tree,
+}
+
+// What the samplers know about a light.
+pub(crate) struct LightInfo {
+    pub power: f64,
+    pub bounds: Option<Aabb>,
+    pub hittable: bool,
+}
+
+pub(crate) enum LightSampler {
+    Uniform(usize),
+    Power(Distribution1D),
+    Tree(LightTree),
+}
+
+impl LightSampler {
+    pub fn new(sampling: LightSampling, lights: &[LightInfo]) -> Self {
+        if lights.is_empty() {
+            return Self::Uniform(0);
+        }
+        // Lights with unknown power are treated as average ones rather than
+        // never being sampled.
+        let known = lights
+            .iter()
+            .map(|light| light.power)
+            .filter(|power| *power > 0.0 && power.is_finite())
+            .collect::<Vec<_>>();
+        let average = if known.is_empty() {
+            1.0
+        } else {
+            known.iter().sum::<f64>() / known.len() as f64
+        };
+        let powers = lights
+            .iter()
+            .map(|light| {
+                if light.power > 0.0 && light.power.is_finite() {
+                    light.power
+                } else {
+                    average
+                }
+            })
+            .collect::<Vec<_>>();
+
+        match sampling {
+            LightSampling::Uniform => Self::Uniform(lights.len()),
+            LightSampling::Power => Self::Power(Distribution1D::new(powers)),
+            LightSampling::Tree => Self::Tree(LightTree::new(lights, &powers)),
+        }
+    }
+
+    // Picks a light for `point`, returning its index and probability.
+    pub fn sample(&self, point: &Point) -> Option<(usize, f64)> {
+        match self {
+            Self::Uniform(0) => None,
+            Self::Uniform(count) => {
+                let index = ((f64::random() * *count as f64) as usize).min(count - 1);
+                Some((index, 1.0 / *count as f64))
+            }
+            Self::Power(distribution) => Some(distribution.sample_discrete(f64::random())),
+            Self::Tree(tree) => tree.sample(point),
+        }
+    }
+
+    pub fn pmf(&self, point: &Point, index: usize) -> f64 {
+        match self {
+            Self::Uniform(0) => 0.0,
+            Self::Uniform(count) => 1.0 / *count as f64,
+            Self::Power(distribution) => distribution.discrete_pdf(index),
+            Self::Tree(tree) => tree.pmf(point, index),
+        }
+    }
+}
+
+struct LightNode {
+    bounds: Aabb,
+    power: f64,
+    parent: Option<usize>,
+    content: NodeContent,
+}
+
+enum NodeContent {
+    Light(usize),
+    Children(usize, usize),
+}
+
+// Binary tree over the lights with bounds, while lights at infinity are
+// picked uniformly beside it.
+pub(crate) struct LightTree {
+    nodes: Vec<LightNode>,
+    // Node of each bounded light, `None` for lights at infinity.
+    leaves: Vec<Option<usize>>,
+    infinite: Vec<usize>,
+}
+
+impl LightTree {
+    fn new(lights: &[LightInfo], powers: &[f64]) -> Self {
+        let mut tree = Self {
+            nodes: Vec::new(),
+            leaves: vec![None; lights.len()],
+            infinite: Vec::new(),
+        };
+        let mut bounded = Vec::new();
+        for (index, light) in lights.iter().enumerate() {
+            match light.bounds {
+                Some(bounds) => bounded.push((index, bounds, powers[index])),
+                None => tree.infinite.push(index),
+            }
+        }
+        if !bounded.is_empty() {
+            tree.build(bounded, None);
+        }
+        tree
+    }
+
+    fn build(&mut self, mut lights: Vec<(usize, Aabb, f64)>, parent: Option<usize>) -> usize {
+        let node = self.nodes.len();
+        let bounds = lights
+            .iter()
+            .map(|(_, bounds, _)| *bounds)
+            .reduce(|a, b| a.surrounding(&b))
+            .unwrap();
+        let power = lights.iter().map(|(_, _, power)| power).sum();
+        self.nodes.push(LightNode {
+            bounds,
+            power,
+            parent,
+            content: NodeContent::Light(lights[0].0),
+        });
+
+        if lights.len() == 1 {
+            self.leaves[lights[0].0] = Some(node);
+            return node;
+        }
+
+        let centroids = lights
+            .iter()
+            .map(|(_, bounds, _)| Aabb::new(bounds.centroid(), bounds.centroid()))
+            .reduce(|a, b| a.surrounding(&b))
+            .unwrap();
+        let axis = centroids.longest_axis();
+        lights.sort_by(|(_, a, _), (_, b, _)| {
+            a.centroid()[axis]
+                .partial_cmp(&b.centroid()[axis])
+                .unwrap_or(Ordering::Equal)
+        });
+        let rest = lights.split_off(lights.len() / 2);
+        let left = self.build(lights, Some(node));
+        let right = self.build(rest, Some(node));
+        self.nodes[node].content = NodeContent::Children(left, right);
+        node
+    }
+
+    fn infinite_probability(&self) -> f64 {
+        let bounded = usize::from(!self.nodes.is_empty());
+        self.infinite.len() as f64 / (self.infinite.len() + bounded) as f64
+    }
+
+    // Power over squared distance, not letting the distance drop below the
+    // node's own size so nearby nodes are not overly favoured.
+    fn importance(&self, node: usize, point: &Point) -> f64 {
+        let node = &self.nodes[node];
+        let half = 0.5 * (node.bounds.maximum - node.bounds.minimum).len();
+        let distance_squared = (node.bounds.centroid() - *point).len_squared();
+        node.power / distance_squared.max(half * half).max(1e-8)
+    }
+
+    fn split(&self, left: usize, right: usize, point: &Point) -> f64 {
+        let left = self.importance(left, point);
+        let right = self.importance(right, point);
+        if left + right > 0.0 {
+            left / (left + right)
+        } else {
+            0.5
+        }
+    }
+
+    fn sample(&self, point: &Point) -> Option<(usize, f64)> {
+        let p_infinite = self.infinite_probability();
+        if f64::random() < p_infinite {
+            let count = self.infinite.len();
+            let index = ((f64::random() * count as f64) as usize).min(count - 1);
+            return Some((self.infinite[index], p_infinite / count as f64));
+        }
+        if self.nodes.is_empty() {
+            return None;
+        }
+
+        let mut pmf = 1.0 - p_infinite;
+        let mut node = 0;
+        loop {
+            match self.nodes[node].content {
+                NodeContent::Light(index) => return Some((index, pmf)),
+                NodeContent::Children(left, right) => {
+                    let p_left = self.split(left, right, point);
+                    if f64::random() < p_left {
+                        pmf *= p_left;
+                        node = left;
+                    } else {
+                        pmf *= 1.0 - p_left;
+                        node = right;
+                    }
+                }
+            }
+        }
+    }
+
+    fn pmf(&self, point: &Point, index: usize) -> f64 {
+        let p_infinite = self.infinite_probability();
+        let mut node = match self.leaves[index] {
+            Some(node) => node,
+            None => return p_infinite / self.infinite.len() as f64,
+        };
+
+        let mut pmf = 1.0 - p_infinite;
+        while let Some(parent) = self.nodes[node].parent {
+            if let NodeContent::Children(left, right) = self.nodes[parent].content {
+                let p_left = self.split(left, right, point);
+                pmf *= if node == left { p_left } else { 1.0 - p_left };
+            }
+            node = parent;
+        }
+        pmf
+    }
+}
+
+// Hierarchy over the bounds of the lights rays can hit, to tell which of
+// them a ray has reached. The root is the first node.
+pub(crate) struct LightLookup {
+    nodes: Vec<(Aabb, NodeContent)>,
+    // Hittable lights without bounds, always candidates.
+    unbounded: Vec<usize>,
+}
+
+impl LightLookup {
+    pub fn new(lights: &[LightInfo]) -> Self {
+        let mut lookup = Self {
+            nodes: Vec::new(),
+            unbounded: Vec::new(),
+        };
+        let mut bounded = Vec::new();
+        for (index, light) in lights.iter().enumerate() {
+            match light.bounds {
+                // Grown a little so points found on the surface stay inside.
+                Some(bounds) if light.hittable => {
+                    let margin = v3!(1e-4 * (bounds.maximum - bounds.minimum).len().max(1.0));
+                    bounded.push((
+                        Aabb::new(bounds.minimum - margin, bounds.maximum + margin),
+                        index,
+                    ));
+                }
+                None if light.hittable => lookup.unbounded.push(index),
+                _ => {}
+            }
+        }
+        if !bounded.is_empty() {
+            lookup.build(&BvhBuilder::new().leaf_size(1), bounded);
+        }
+        lookup
+    }
+
+    fn build(&mut self, builder: &BvhBuilder, lights: Vec<(Aabb, usize)>) -> usize {
+        let node = self.nodes.len();
+        self.nodes
+            .push((bvh::bounds(&lights), NodeContent::Light(lights[0].1)));
+        // Leaves of one light each.
+        if let Ok((left, right)) = builder.split(lights) {
+            let left = self.build(builder, left);
+            let right = self.build(builder, right);
+            self.nodes[node].1 = NodeContent::Children(left, right);
+        }
+        node
+    }
+
+    // The first light around `point` that `found` accepts.
+    pub fn find<F>(&self, point: &Point, mut found: F) -> Option<usize>
+    where
+        F: FnMut(usize) -> bool,
+    {
+        if !self.nodes.is_empty() {
+            if let Some(index) = self.find_in(0, point, &mut found) {
+                return Some(index);
+            }
+        }
+        self.unbounded.iter().copied().find(|&index| found(index))
+    }
+
+    fn find_in<F>(&self, node: usize, point: &Point, found: &mut F) -> Option<usize>
+    where
+        F: FnMut(usize) -> bool,
+    {
+        let (bounds, content) = &self.nodes[node];
+        if !bounds.contains(point) {
+            return None;
+        }
+        match *content {
+            NodeContent::Light(index) => Some(index).filter(|&index| found(index)),
+            NodeContent::Children(left, right) => self
+                .find_in(left, point, found)
+                .or_else(|| self.find_in(right, point, found)),
+        }
+    }
+}
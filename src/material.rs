@@ -1,102 +1,416 @@
-use std::sync::Arc;
+use std::{f64::consts::PI, sync::Arc};
 
 use crate::{
     color,
     hit::HitRecord,
+    microfacet::{fresnel_conductor, ConductorLobe, DielectricLobe, LobePdf, TrowbridgeReitz},
+    pdf::{CosinePdf, Pdf},
+    principled::Principled,
     random::Random,
     ray::Ray,
+    spectrum::{sample_wavelength, wavelength_to_rgb, RefractiveIndex},
+    texture::{SolidColor, Texture},
     vec3::{Color, Vec3},
 };
 
-pub enum Material {
-    Lambertian { albedo: Color },
-    Metal { albedo: Color, fuzz: f64 },
-    Dielectric { refraction_index: f64 },
+pub enum Scatter {
+    // A single continuation ray, such as a mirror reflection.
+    Specular { ray: Ray, attenuation: Color },
+    // A distribution to draw directions from, weighted by `Bsdf::eval`. It
+    // should be nonzero wherever `eval` is.
+    Sampled { pdf: Box<dyn Pdf> },
 }
 
+// How a surface scatters and emits light. Implement it to add materials of
+// your own; directions are in world space, like `ray_in.direction`.
+pub trait Bsdf: Send + Sync {
+    // How `ray_in` continues after hitting the surface, or `None` if it is
+    // absorbed.
+    fn scatter(&self, ray_in: &Ray, record: &HitRecord) -> Option<Scatter>;
+
+    // BSDF times the cosine of `direction`, for materials that scatter with
+    // `Scatter::Sampled`.
+    fn eval(&self, _ray_in: &Ray, _record: &HitRecord, _direction: &Vec3) -> Color {
+        color!(0)
+    }
+
+    // Density of `Scatter::Sampled` picking `scattered`, used to weigh light
+    // samples against BSDF samples. Specular materials have none to give.
+    fn pdf(&self, _ray_in: &Ray, _record: &HitRecord, _scattered: &Vec3) -> f64 {
+        0.0
+    }
+
+    fn emitted(&self, _record: &HitRecord) -> Color {
+        color!(0)
+    }
+}
+
+// Shapes hold their material as `Arc<Material>`.
+pub type Material = dyn Bsdf;
+
 impl Material {
     pub fn new_lambertian(albedo: Color) -> Arc<Self> {
-        Arc::new(Self::Lambertian { albedo })
+        Self::new_lambertian_texture(SolidColor::new(albedo))
+    }
+
+    pub fn new_lambertian_texture(albedo: Arc<dyn Texture>) -> Arc<Self> {
+        Arc::new(Lambertian { albedo })
     }
 
     pub fn new_metal(albedo: Color, fuzz: f64) -> Arc<Self> {
-        Arc::new(Self::Metal {
+        Arc::new(Metal {
             albedo,
             fuzz: if fuzz < 1.0 { fuzz } else { 1.0 },
         })
     }
 
     pub fn new_dielectric(refraction_index: f64) -> Arc<Self> {
-        Arc::new(Self::Dielectric { refraction_index })
+        Self::new_absorbing_dielectric(refraction_index, color!(0))
     }
 
-    pub fn scatter(&self, ray_in: &Ray, record: &HitRecord) -> Option<(Ray, Color)> {
-        match *self {
-            Self::Lambertian { albedo } => Self::scatter_lambertion(record, albedo),
-            Self::Metal { albedo, fuzz } => Self::scatter_metal(ray_in, record, albedo, fuzz),
-            Self::Dielectric { refraction_index } => {
-                Self::scatter_dielectric(ray_in, record, refraction_index)
-            }
-        }
+    // Glass or liquid absorbing light as it travels through, `absorption`
+    // being the Beer-Lambert coefficient per unit distance for each channel.
+    // It is applied when a ray leaves through the inside of the surface, so
+    // a stretch of the medium that ends on some other object inside it, such
+    // as ice in a drink, absorbs nothing.
+    pub fn new_absorbing_dielectric(refraction_index: f64, absorption: Color) -> Arc<Self> {
+        Self::new_dispersive_dielectric(RefractiveIndex::Constant(refraction_index), absorption)
     }
 
-    fn scatter_lambertion(record: &HitRecord, albedo: Color) -> Option<(Ray, Color)> {
-        let mut scatter_direction = record.normal + Vec3::random_unit_vector();
-        if scatter_direction.is_near_zero() {
-            scatter_direction = record.normal
+    // Glass whose index varies with wavelength, splitting white light into
+    // its colours.
+    pub fn new_dispersive_dielectric(
+        refraction_index: RefractiveIndex,
+        absorption: Color,
+    ) -> Arc<Self> {
+        Arc::new(Dielectric {
+            refraction_index,
+            absorption,
+        })
+    }
+
+    // Like `new_absorbing_dielectric`, where `transmittance` is what is left
+    // of white light after `distance` through the medium. `None` unless the
+    // distance is positive.
+    pub fn new_tinted_dielectric(
+        refraction_index: f64,
+        transmittance: Color,
+        distance: f64,
+    ) -> Option<Arc<Self>> {
+        if distance <= 0.0 || distance.is_nan() {
+            return None;
         }
-        Some((Ray::new(record.point, scatter_direction), albedo))
-    }
-
-    fn scatter_metal(
-        ray_in: &Ray,
-        record: &HitRecord,
-        albedo: Color,
-        fuzz: f64,
-    ) -> Option<(Ray, Color)> {
-        let reflected = ray_in.direction.unit().reflect(&record.normal);
-        let scattered = Ray::new(
-            record.point,
-            reflected + fuzz * Vec3::random_in_unit_sphere(),
+        let coefficient = |t: f64| -t.clamp(1e-6, 1.0).ln() / distance;
+        let absorption = color!(
+            coefficient(transmittance.x),
+            coefficient(transmittance.y),
+            coefficient(transmittance.z)
         );
-        if scattered.direction.dot(&record.normal) > 0.0 {
-            Some((scattered, albedo))
+        Some(Self::new_absorbing_dielectric(refraction_index, absorption))
+    }
+
+    pub fn new_conductor(eta: Color, k: Color, roughness: f64) -> Arc<Self> {
+        Arc::new(Conductor {
+            eta,
+            k,
+            roughness: roughness.clamp(0.0, 1.0),
+        })
+    }
+
+    // Indices at roughly 650, 550 and 450nm.
+    pub fn new_gold(roughness: f64) -> Arc<Self> {
+        Self::new_conductor(
+            color!(0.143, 0.374, 1.442),
+            color!(3.983, 2.385, 1.603),
+            roughness,
+        )
+    }
+
+    pub fn new_copper(roughness: f64) -> Arc<Self> {
+        Self::new_conductor(
+            color!(0.200, 0.924, 1.102),
+            color!(3.912, 2.452, 2.142),
+            roughness,
+        )
+    }
+
+    pub fn new_aluminium(roughness: f64) -> Arc<Self> {
+        Self::new_conductor(
+            color!(1.657, 0.880, 0.521),
+            color!(9.224, 6.270, 4.837),
+            roughness,
+        )
+    }
+
+    pub fn new_rough_dielectric(refraction_index: f64, roughness: f64) -> Arc<Self> {
+        Arc::new(RoughDielectric {
+            refraction_index,
+            roughness: roughness.clamp(0.0, 1.0),
+        })
+    }
+
+    pub fn new_principled(principled: Principled) -> Arc<Self> {
+        Arc::new(principled)
+    }
+
+    pub fn new_diffuse_light(emit: Color) -> Arc<Self> {
+        Self::new_diffuse_light_texture(SolidColor::new(emit), false)
+    }
+
+    pub fn new_diffuse_light_texture(emit: Arc<dyn Texture>, two_sided: bool) -> Arc<Self> {
+        Arc::new(DiffuseLight { emit, two_sided })
+    }
+}
+
+pub struct Lambertian {
+    albedo: Arc<dyn Texture>,
+}
+
+impl Bsdf for Lambertian {
+    fn scatter(&self, _ray_in: &Ray, record: &HitRecord) -> Option<Scatter> {
+        Some(Scatter::Sampled {
+            pdf: Box::new(CosinePdf::new(&record.normal)),
+        })
+    }
+
+    fn eval(&self, _ray_in: &Ray, record: &HitRecord, direction: &Vec3) -> Color {
+        let cosine = record.normal.dot(&direction.unit());
+        if cosine < 0.0 {
+            color!(0)
         } else {
-            None
+            self.albedo.value(record.u, record.v, &record.point) * cosine / PI
         }
     }
 
-    fn scatter_dielectric(
-        ray_in: &Ray,
-        record: &HitRecord,
-        refraction_index: f64,
-    ) -> Option<(Ray, Color)> {
-        let attenuation = color!(1);
-        let refraction_ratio = if record.is_front_face {
-            1.0 / refraction_index
+    fn pdf(&self, _ray_in: &Ray, record: &HitRecord, scattered: &Vec3) -> f64 {
+        CosinePdf::new(&record.normal).value(scattered)
+    }
+}
+
+pub struct Metal {
+    albedo: Color,
+    fuzz: f64,
+}
+
+impl Bsdf for Metal {
+    fn scatter(&self, ray_in: &Ray, record: &HitRecord) -> Option<Scatter> {
+        scatter_metal(ray_in, record, self.albedo, self.fuzz)
+    }
+}
+
+pub struct Dielectric {
+    refraction_index: RefractiveIndex,
+    absorption: Color,
+}
+
+impl Bsdf for Dielectric {
+    fn scatter(&self, ray_in: &Ray, record: &HitRecord) -> Option<Scatter> {
+        // Hitting the inside of the surface ends a stretch through the medium.
+        let mut attenuation = if record.is_front_face {
+            color!(1)
         } else {
-            refraction_index
+            let distance = record.t * ray_in.direction.len();
+            let optical_depth = -distance * self.absorption;
+            color!(
+                optical_depth.x.exp(),
+                optical_depth.y.exp(),
+                optical_depth.z.exp()
+            )
         };
+        // A dispersive medium follows a single wavelength from here on,
+        // weighted by the colour it contributes.
+        let (refraction_index, wavelength) = if !self.refraction_index.is_dispersive() {
+            (self.refraction_index.nominal(), ray_in.wavelength)
+        } else if let Some(wavelength) = ray_in.wavelength {
+            (self.refraction_index.at(wavelength), Some(wavelength))
+        } else {
+            let wavelength = sample_wavelength();
+            attenuation *= wavelength_to_rgb(wavelength);
+            (self.refraction_index.at(wavelength), Some(wavelength))
+        };
+        scatter_dielectric(ray_in, record, refraction_index, attenuation, wavelength)
+    }
+}
+
+// Rough metal with a complex index of refraction `eta + i k` per channel.
+pub struct Conductor {
+    eta: Color,
+    k: Color,
+    roughness: f64,
+}
+
+impl Conductor {
+    fn lobe(&self) -> ConductorLobe {
+        ConductorLobe {
+            distribution: TrowbridgeReitz::new(self.roughness),
+            eta: self.eta,
+            k: self.k,
+        }
+    }
+}
 
-        let unit_direction = ray_in.direction.unit();
-        let cos_theta = (-unit_direction).dot(&record.normal).min(1.0);
-        let sin_theta = (1.0 - cos_theta * cos_theta).sqrt();
+impl Bsdf for Conductor {
+    fn scatter(&self, ray_in: &Ray, record: &HitRecord) -> Option<Scatter> {
+        let lobe = self.lobe();
+        if lobe.distribution.is_smooth() {
+            return scatter_smooth_conductor(ray_in, record, &lobe);
+        }
+        let wo = -ray_in.direction;
+        Some(Scatter::Sampled {
+            pdf: Box::new(LobePdf::new(&record.normal, &wo, lobe)),
+        })
+    }
+
+    fn eval(&self, ray_in: &Ray, record: &HitRecord, direction: &Vec3) -> Color {
+        let wo = -ray_in.direction;
+        LobePdf::new(&record.normal, &wo, self.lobe()).eval(direction)
+    }
 
-        let cannot_refract = refraction_ratio * sin_theta > 1.0;
-        let direction = if cannot_refract
-            || Material::reflectance(cos_theta, refraction_ratio) > f64::random()
-        {
-            unit_direction.reflect(&record.normal)
+    fn pdf(&self, ray_in: &Ray, record: &HitRecord, scattered: &Vec3) -> f64 {
+        let lobe = self.lobe();
+        if lobe.distribution.is_smooth() {
+            return 0.0;
+        }
+        let wo = -ray_in.direction;
+        LobePdf::new(&record.normal, &wo, lobe).value(scattered)
+    }
+}
+
+pub struct RoughDielectric {
+    refraction_index: f64,
+    roughness: f64,
+}
+
+impl RoughDielectric {
+    fn lobe(&self, record: &HitRecord) -> DielectricLobe {
+        DielectricLobe {
+            distribution: TrowbridgeReitz::new(self.roughness),
+            eta: relative_index(record, self.refraction_index),
+        }
+    }
+}
+
+impl Bsdf for RoughDielectric {
+    fn scatter(&self, ray_in: &Ray, record: &HitRecord) -> Option<Scatter> {
+        let lobe = self.lobe(record);
+        if lobe.distribution.is_smooth() {
+            return scatter_dielectric(
+                ray_in,
+                record,
+                self.refraction_index,
+                color!(1),
+                ray_in.wavelength,
+            );
+        }
+        let wo = -ray_in.direction;
+        Some(Scatter::Sampled {
+            pdf: Box::new(LobePdf::new(&record.normal, &wo, lobe)),
+        })
+    }
+
+    fn eval(&self, ray_in: &Ray, record: &HitRecord, direction: &Vec3) -> Color {
+        let wo = -ray_in.direction;
+        LobePdf::new(&record.normal, &wo, self.lobe(record)).eval(direction)
+    }
+
+    fn pdf(&self, ray_in: &Ray, record: &HitRecord, scattered: &Vec3) -> f64 {
+        let lobe = self.lobe(record);
+        if lobe.distribution.is_smooth() {
+            return 0.0;
+        }
+        let wo = -ray_in.direction;
+        LobePdf::new(&record.normal, &wo, lobe).value(scattered)
+    }
+}
+
+pub struct DiffuseLight {
+    emit: Arc<dyn Texture>,
+    two_sided: bool,
+}
+
+impl Bsdf for DiffuseLight {
+    fn scatter(&self, _ray_in: &Ray, _record: &HitRecord) -> Option<Scatter> {
+        None
+    }
+
+    fn emitted(&self, record: &HitRecord) -> Color {
+        if self.two_sided || record.is_front_face {
+            self.emit.value(record.u, record.v, &record.point)
         } else {
-            unit_direction.refract(&record.normal, refraction_ratio)
-        };
+            color!(0)
+        }
+    }
+}
 
-        Some((Ray::new(record.point, direction), attenuation))
+// Index beyond the surface relative to the side the ray comes from.
+fn relative_index(record: &HitRecord, refraction_index: f64) -> f64 {
+    if record.is_front_face {
+        refraction_index
+    } else {
+        1.0 / refraction_index
     }
+}
 
-    fn reflectance(cosine: f64, ref_idx: f64) -> f64 {
-        let r0 = (1.0 - ref_idx) / (1.0 + ref_idx);
-        let r0 = r0 * r0;
-        r0 + (1.0 - r0) * (1.0 - cosine).powi(5)
+fn scatter_smooth_conductor(
+    ray_in: &Ray,
+    record: &HitRecord,
+    lobe: &ConductorLobe,
+) -> Option<Scatter> {
+    let unit_direction = ray_in.direction.unit();
+    let cosine = (-unit_direction).dot(&record.normal);
+    Some(Scatter::Specular {
+        ray: ray_in.spawn(record.point, unit_direction.reflect(&record.normal)),
+        attenuation: fresnel_conductor(cosine, &lobe.eta, &lobe.k),
+    })
+}
+
+fn scatter_metal(ray_in: &Ray, record: &HitRecord, albedo: Color, fuzz: f64) -> Option<Scatter> {
+    let reflected = ray_in.direction.unit().reflect(&record.normal);
+    let scattered = ray_in.spawn(
+        record.point,
+        reflected + fuzz * Vec3::random_in_unit_sphere(),
+    );
+    if scattered.direction.dot(&record.normal) > 0.0 {
+        Some(Scatter::Specular {
+            ray: scattered,
+            attenuation: albedo,
+        })
+    } else {
+        None
     }
 }
+
+fn scatter_dielectric(
+    ray_in: &Ray,
+    record: &HitRecord,
+    refraction_index: f64,
+    attenuation: Color,
+    wavelength: Option<f64>,
+) -> Option<Scatter> {
+    let refraction_ratio = if record.is_front_face {
+        1.0 / refraction_index
+    } else {
+        refraction_index
+    };
+
+    let unit_direction = ray_in.direction.unit();
+    let cos_theta = (-unit_direction).dot(&record.normal).min(1.0);
+    let sin_theta = (1.0 - cos_theta * cos_theta).sqrt();
+
+    let cannot_refract = refraction_ratio * sin_theta > 1.0;
+    let direction = if cannot_refract || reflectance(cos_theta, refraction_ratio) > f64::random() {
+        unit_direction.reflect(&record.normal)
+    } else {
+        unit_direction.refract(&record.normal, refraction_ratio)
+    };
+
+    let mut ray = ray_in.spawn(record.point, direction);
+    ray.wavelength = wavelength;
+    Some(Scatter::Specular { ray, attenuation })
+}
+
+fn reflectance(cosine: f64, ref_idx: f64) -> f64 {
+    let r0 = (1.0 - ref_idx) / (1.0 + ref_idx);
+    let r0 = r0 * r0;
+    r0 + (1.0 - r0) * (1.0 - cosine).powi(5)
+}
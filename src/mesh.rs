@@ -0,0 +1,278 @@
+use std::{error, fmt, sync::Arc};
+
+use crate::{
+    aabb::Aabb,
+    bvh::{self, BvhBuilder},
+    hit::{Hit, HitRecord},
+    material::Material,
+    ray::Ray,
+    triangle, v3,
+    vec3::{Point, Vec3},
+};
+
+#[derive(Default)]
+pub struct MeshData {
+    pub positions: Vec<Point>,
+    pub normals: Vec<Vec3>,
+    pub uvs: Vec<(f64, f64)>,
+    pub indices: Vec<[usize; 3]>,
+    pub material_ids: Vec<usize>,
+}
+
+#[derive(Debug)]
+pub enum MeshError {
+    NoFaces,
+    NoMaterials,
+    TooManyFaces(usize),
+    IndexOutOfRange {
+        face: usize,
+        index: usize,
+    },
+    // Per-vertex or per-face attributes must be absent or cover every one.
+    AttributeCount {
+        attribute: &'static str,
+        expected: usize,
+        found: usize,
+    },
+    MaterialOutOfRange {
+        face: usize,
+        id: usize,
+    },
+}
+
+impl fmt::Display for MeshError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NoFaces => write!(f, "mesh has no faces"),
+            Self::NoMaterials => write!(f, "mesh has no materials"),
+            Self::TooManyFaces(count) => write!(f, "mesh has too many faces ({})", count),
+            Self::IndexOutOfRange { face, index } => {
+                write!(f, "face {} uses vertex {} out of range", face, index)
+            }
+            Self::AttributeCount {
+                attribute,
+                expected,
+                found,
+            } => write!(
+                f,
+                "mesh has {} {} where {} were expected",
+                found, attribute, expected
+            ),
+            Self::MaterialOutOfRange { face, id } => {
+                write!(f, "face {} uses material {} out of range", face, id)
+            }
+        }
+    }
+}
+
+impl error::Error for MeshError {}
+
+struct Shared {
+    data: MeshData,
+    materials: Vec<Arc<Material>>,
+}
+
+pub struct TriangleMesh {
+    shared: Arc<Shared>,
+    bvh: MeshBvh,
+}
+
+impl TriangleMesh {
+    pub fn new(data: MeshData, material: Arc<Material>) -> Result<Box<Self>, MeshError> {
+        Self::with_materials(data, vec![material])
+    }
+
+    pub fn with_materials(
+        data: MeshData,
+        materials: Vec<Arc<Material>>,
+    ) -> Result<Box<Self>, MeshError> {
+        validate(&data, &materials)?;
+        let bvh = MeshBvh::new(&data);
+        let shared = Arc::new(Shared { data, materials });
+        Ok(Box::new(Self { shared, bvh }))
+    }
+
+    pub fn triangle_count(&self) -> usize {
+        self.shared.data.indices.len()
+    }
+
+    fn vertices(&self, face: usize) -> [Point; 3] {
+        vertices(&self.shared.data, face)
+    }
+
+    fn record(&self, ray: &Ray, face: usize, t: f64, b1: f64, b2: f64) -> HitRecord<'_> {
+        let data = &self.shared.data;
+        let [i0, i1, i2] = data.indices[face];
+        let normals = if data.normals.is_empty() {
+            None
+        } else {
+            Some([data.normals[i0], data.normals[i1], data.normals[i2]])
+        };
+        let uvs = if data.uvs.is_empty() {
+            None
+        } else {
+            Some([data.uvs[i0], data.uvs[i1], data.uvs[i2]])
+        };
+        let material_id = data.material_ids.get(face).copied().unwrap_or(0);
+
+        triangle::record(
+            ray,
+            t,
+            v3!(1.0 - b1 - b2, b1, b2),
+            &self.vertices(face),
+            normals.as_ref(),
+            uvs.as_ref(),
+            &*self.shared.materials[material_id],
+        )
+    }
+
+    // Closest face along `ray` below `t_max` in the subtree at `node`, with
+    // its distance and barycentrics.
+    fn hit_node(
+        &self,
+        node: usize,
+        ray: &Ray,
+        t_min: f64,
+        t_max: f64,
+    ) -> Option<(usize, f64, f64, f64)> {
+        let node = &self.bvh.nodes[node];
+        if !node.bbox.hit(ray, t_min, t_max) {
+            return None;
+        }
+        match node.content {
+            NodeContent::Leaf { start, count } => {
+                let mut closest = None;
+                let mut t_max = t_max;
+                for &face in &self.bvh.faces[start as usize..(start + count) as usize] {
+                    let face = face as usize;
+                    if let Some((t, b1, b2)) =
+                        triangle::intersect(&self.vertices(face), ray, t_min, t_max)
+                    {
+                        t_max = t;
+                        closest = Some((face, t, b1, b2));
+                    }
+                }
+                closest
+            }
+            NodeContent::Branch { left, right } => {
+                let left = self.hit_node(left as usize, ray, t_min, t_max);
+                let t_max = left.map_or(t_max, |(_, t, _, _)| t);
+                self.hit_node(right as usize, ray, t_min, t_max).or(left)
+            }
+        }
+    }
+}
+
+impl Hit for TriangleMesh {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        let (face, t, b1, b2) = self.hit_node(0, ray, t_min, t_max)?;
+        Some(self.record(ray, face, t, b1, b2))
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(self.bvh.nodes[0].bbox)
+    }
+}
+
+fn validate(data: &MeshData, materials: &[Arc<Material>]) -> Result<(), MeshError> {
+    if data.indices.is_empty() {
+        return Err(MeshError::NoFaces);
+    }
+    if data.indices.len() > u32::MAX as usize {
+        return Err(MeshError::TooManyFaces(data.indices.len()));
+    }
+    if materials.is_empty() {
+        return Err(MeshError::NoMaterials);
+    }
+    for (face, indices) in data.indices.iter().enumerate() {
+        if let Some(&index) = indices.iter().find(|&&i| i >= data.positions.len()) {
+            return Err(MeshError::IndexOutOfRange { face, index });
+        }
+    }
+
+    let vertex_count = data.positions.len();
+    let face_count = data.indices.len();
+    for (attribute, expected, found) in [
+        ("normals", vertex_count, data.normals.len()),
+        ("uvs", vertex_count, data.uvs.len()),
+        ("material ids", face_count, data.material_ids.len()),
+    ] {
+        if found != 0 && found != expected {
+            return Err(MeshError::AttributeCount {
+                attribute,
+                expected,
+                found,
+            });
+        }
+    }
+    if let Some((face, &id)) = data
+        .material_ids
+        .iter()
+        .enumerate()
+        .find(|(_, &id)| id >= materials.len())
+    {
+        return Err(MeshError::MaterialOutOfRange { face, id });
+    }
+    Ok(())
+}
+
+fn vertices(data: &MeshData, face: usize) -> [Point; 3] {
+    let [i0, i1, i2] = data.indices[face];
+    [data.positions[i0], data.positions[i1], data.positions[i2]]
+}
+
+enum NodeContent {
+    // Faces `faces[start..start + count]`.
+    Leaf { start: u32, count: u32 },
+    Branch { left: u32, right: u32 },
+}
+
+struct MeshNode {
+    bbox: Aabb,
+    content: NodeContent,
+}
+
+// Hierarchy over face numbers rather than boxed triangles, so a mesh costs
+// one node array and one index per face on top of its buffers. The root is
+// the first node.
+struct MeshBvh {
+    nodes: Vec<MeshNode>,
+    faces: Vec<u32>,
+}
+
+impl MeshBvh {
+    fn new(data: &MeshData) -> Self {
+        let faces = (0..data.indices.len())
+            .map(|face| (triangle::bounds(&vertices(data, face)), face as u32))
+            .collect::<Vec<_>>();
+        let mut bvh = Self {
+            nodes: Vec::new(),
+            faces: Vec::with_capacity(faces.len()),
+        };
+        bvh.build(&BvhBuilder::new(), faces);
+        bvh
+    }
+
+    fn build(&mut self, builder: &BvhBuilder, faces: Vec<(Aabb, u32)>) -> u32 {
+        let node = self.nodes.len();
+        self.nodes.push(MeshNode {
+            bbox: bvh::bounds(&faces),
+            content: NodeContent::Leaf { start: 0, count: 0 },
+        });
+        self.nodes[node].content = match builder.split(faces) {
+            Ok((left, right)) => NodeContent::Branch {
+                left: self.build(builder, left),
+                right: self.build(builder, right),
+            },
+            Err(faces) => {
+                let start = self.faces.len() as u32;
+                self.faces.extend(faces.iter().map(|(_, face)| *face));
+                NodeContent::Leaf {
+                    start,
+                    count: faces.len() as u32,
+                }
+            }
+        };
+        node as u32
+    }
+}
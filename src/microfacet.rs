@@ -0,0 +1,419 @@
+use std::{f64::consts::PI, ops};
+
+use crate::{
+    color,
+    onb::Onb,
+    pdf::Pdf,
+    random::Random,
+    v3,
+    vec3::{Color, Vec3},
+};
+
+// Isotropic Trowbridge-Reitz (GGX) distribution of microfacet normals, in a
+// local frame where the surface normal is +z.
+#[derive(Clone, Copy, Debug)]
+pub struct TrowbridgeReitz {
+    alpha: f64,
+}
+
+impl TrowbridgeReitz {
+    // Perceptual roughness in [0, 1], squared as in most authoring tools.
+    pub fn new(roughness: f64) -> Self {
+        Self {
+            alpha: roughness.clamp(0.0, 1.0).powi(2),
+        }
+    }
+
+    pub fn alpha(&self) -> f64 {
+        self.alpha
+    }
+
+    // Below this the lobe is narrower than we can sample reliably, so
+    // materials fall back to perfect specular scattering.
+    pub fn is_smooth(&self) -> bool {
+        self.alpha < 1e-3
+    }
+
+    pub fn d(&self, wm: &Vec3) -> f64 {
+        let cos2 = wm.z * wm.z;
+        if cos2 <= 0.0 {
+            return 0.0;
+        }
+        let tan2 = (1.0 - cos2).max(0.0) / cos2;
+        let alpha2 = self.alpha * self.alpha;
+        let e = 1.0 + tan2 / alpha2;
+        1.0 / (PI * alpha2 * cos2 * cos2 * e * e)
+    }
+
+    fn lambda(&self, w: &Vec3) -> f64 {
+        let cos2 = w.z * w.z;
+        if cos2 <= 0.0 {
+            return 0.0;
+        }
+        let tan2 = (1.0 - cos2).max(0.0) / cos2;
+        ((1.0 + self.alpha * self.alpha * tan2).sqrt() - 1.0) / 2.0
+    }
+
+    pub fn g1(&self, w: &Vec3) -> f64 {
+        1.0 / (1.0 + self.lambda(w))
+    }
+
+    pub fn g(&self, wo: &Vec3, wi: &Vec3) -> f64 {
+        1.0 / (1.0 + self.lambda(wo) + self.lambda(wi))
+    }
+
+    // Density of normals visible from `w`.
+    pub fn visible_d(&self, w: &Vec3, wm: &Vec3) -> f64 {
+        if w.z == 0.0 {
+            return 0.0;
+        }
+        self.g1(w) / w.z.abs() * self.d(wm) * w.dot(wm).abs()
+    }
+
+    // Samples a normal from `visible_d` by stretching to the unit roughness
+    // configuration and sampling the projected hemisphere.
+    pub fn sample_wm(&self, w: &Vec3) -> Vec3 {
+        let mut wh = v3!(self.alpha * w.x, self.alpha * w.y, w.z).unit();
+        if wh.z < 0.0 {
+            wh = -wh;
+        }
+        let t1 = if wh.z < 0.99999 {
+            v3!(0, 0, 1).cross(&wh).unit()
+        } else {
+            v3!(1, 0, 0)
+        };
+        let t2 = wh.cross(&t1);
+
+        let r = f64::random().sqrt();
+        let phi = 2.0 * PI * f64::random();
+        let (px, py) = (r * phi.cos(), r * phi.sin());
+        let h = (1.0 - px * px).sqrt();
+        let s = (1.0 + wh.z) / 2.0;
+        let py = (1.0 - s) * h + s * py;
+        let pz = (1.0 - px * px - py * py).max(0.0).sqrt();
+
+        let nh = px * t1 + py * t2 + pz * wh;
+        v3!(self.alpha * nh.x, self.alpha * nh.y, nh.z.max(1e-6)).unit()
+    }
+}
+
+// Unpolarized Fresnel reflectance of a dielectric, `eta` being the ratio of
+// the indices across the interface as seen from the incident side.
+pub fn fresnel_dielectric(cos_theta_i: f64, eta: f64) -> f64 {
+    let (cos_theta_i, eta) = if cos_theta_i < 0.0 {
+        (-cos_theta_i, 1.0 / eta)
+    } else {
+        (cos_theta_i.min(1.0), eta)
+    };
+    let sin2_theta_t = (1.0 - cos_theta_i * cos_theta_i) / (eta * eta);
+    if sin2_theta_t >= 1.0 {
+        return 1.0;
+    }
+    let cos_theta_t = (1.0 - sin2_theta_t).max(0.0).sqrt();
+    let r_parallel = (eta * cos_theta_i - cos_theta_t) / (eta * cos_theta_i + cos_theta_t);
+    let r_perpendicular = (cos_theta_i - eta * cos_theta_t) / (cos_theta_i + eta * cos_theta_t);
+    (r_parallel * r_parallel + r_perpendicular * r_perpendicular) / 2.0
+}
+
+// Fresnel reflectance of a conductor with complex index `eta + i k`, per
+// channel.
+pub fn fresnel_conductor(cos_theta_i: f64, eta: &Color, k: &Color) -> Color {
+    let channel = |eta: f64, k: f64| {
+        let cos_theta_i = Complex::new(cos_theta_i.clamp(0.0, 1.0), 0.0);
+        let eta = Complex::new(eta, k);
+        let sin2_theta_i = Complex::new(1.0, 0.0) - cos_theta_i * cos_theta_i;
+        let sin2_theta_t = sin2_theta_i / (eta * eta);
+        let cos_theta_t = (Complex::new(1.0, 0.0) - sin2_theta_t).sqrt();
+
+        let r_parallel = (eta * cos_theta_i - cos_theta_t) / (eta * cos_theta_i + cos_theta_t);
+        let r_perpendicular = (cos_theta_i - eta * cos_theta_t) / (cos_theta_i + eta * cos_theta_t);
+        (r_parallel.norm() + r_perpendicular.norm()) / 2.0
+    };
+    color!(
+        channel(eta.x, k.x),
+        channel(eta.y, k.y),
+        channel(eta.z, k.z)
+    )
+}
+
+// Refracts `wi` about `n`, both pointing away from the surface; `eta` is the
+// relative index of the side `n` points away from.
+pub fn refract(wi: &Vec3, n: &Vec3, eta: f64) -> Option<Vec3> {
+    let (n, eta, cos_theta_i) = if wi.dot(n) < 0.0 {
+        (-*n, 1.0 / eta, -wi.dot(n))
+    } else {
+        (*n, eta, wi.dot(n))
+    };
+    let sin2_theta_t = (1.0 - cos_theta_i * cos_theta_i).max(0.0) / (eta * eta);
+    if sin2_theta_t >= 1.0 {
+        return None;
+    }
+    let cos_theta_t = (1.0 - sin2_theta_t).sqrt();
+    Some(-*wi / eta + (cos_theta_i / eta - cos_theta_t) * n)
+}
+
+// Schlick's approximation to Fresnel reflectance, from the reflectance at
+// normal incidence.
+pub fn fresnel_schlick(cos_theta_i: f64, f0: &Color) -> Color {
+    let m = (1.0 - cos_theta_i.clamp(0.0, 1.0)).powi(5);
+    *f0 + (color!(1) - *f0) * m
+}
+
+pub fn reflect(wo: &Vec3, n: &Vec3) -> Vec3 {
+    -*wo + 2.0 * wo.dot(n) * *n
+}
+
+// A scattering lobe in the local frame of the shading normal, `wo` pointing
+// back along the incoming ray.
+pub trait Lobe {
+    // BSDF times the cosine of `wi`.
+    fn eval(&self, wo: &Vec3, wi: &Vec3) -> Color;
+
+    fn pdf(&self, wo: &Vec3, wi: &Vec3) -> f64;
+
+    // May return the zero vector when the sampled microfacet scatters to the
+    // wrong side of the surface, a direction every lobe gives no density.
+    fn sample(&self, wo: &Vec3) -> Vec3;
+}
+
+// GGX reflection off a conductor.
+pub struct ConductorLobe {
+    pub distribution: TrowbridgeReitz,
+    pub eta: Color,
+    pub k: Color,
+}
+
+impl Lobe for ConductorLobe {
+    fn eval(&self, wo: &Vec3, wi: &Vec3) -> Color {
+        if wo.z <= 0.0 || wi.z <= 0.0 {
+            return color!(0);
+        }
+        let wm = *wi + *wo;
+        if wm.len_squared() == 0.0 {
+            return color!(0);
+        }
+        let wm = wm.unit();
+        let fresnel = fresnel_conductor(wo.dot(&wm).abs(), &self.eta, &self.k);
+        self.distribution.d(&wm) * self.distribution.g(wo, wi) * fresnel / (4.0 * wo.z)
+    }
+
+    fn pdf(&self, wo: &Vec3, wi: &Vec3) -> f64 {
+        if wo.z <= 0.0 || wi.z <= 0.0 {
+            return 0.0;
+        }
+        let wm = *wi + *wo;
+        if wm.len_squared() == 0.0 {
+            return 0.0;
+        }
+        let wm = wm.unit();
+        self.distribution.visible_d(wo, &wm) / (4.0 * wo.dot(&wm).abs())
+    }
+
+    fn sample(&self, wo: &Vec3) -> Vec3 {
+        let wi = reflect(wo, &self.distribution.sample_wm(wo));
+        if wi.z > 0.0 {
+            wi
+        } else {
+            Vec3::default()
+        }
+    }
+}
+
+// GGX reflection and transmission through a dielectric interface. `eta` is
+// the index on the far side of the normal relative to the side of `wo`.
+pub struct DielectricLobe {
+    pub distribution: TrowbridgeReitz,
+    pub eta: f64,
+}
+
+impl DielectricLobe {
+    // Generalized half vector, facing +z, or `None` for degenerate or
+    // back-facing configurations.
+    fn half_vector(&self, wo: &Vec3, wi: &Vec3) -> Option<(Vec3, bool)> {
+        if wo.z <= 0.0 || wi.z == 0.0 {
+            return None;
+        }
+        let reflect = wi.z > 0.0;
+        let eta = if reflect { 1.0 } else { self.eta };
+        let wm = *wi * eta + *wo;
+        if wm.len_squared() == 0.0 {
+            return None;
+        }
+        let wm = wm.unit();
+        let wm = if wm.z < 0.0 { -wm } else { wm };
+        if wm.dot(wi) * wi.z < 0.0 || wm.dot(wo) * wo.z < 0.0 {
+            return None;
+        }
+        Some((wm, reflect))
+    }
+}
+
+// As with smooth glass, radiance is not rescaled by eta squared on the way
+// through.
+impl Lobe for DielectricLobe {
+    fn eval(&self, wo: &Vec3, wi: &Vec3) -> Color {
+        let (wm, reflect) = match self.half_vector(wo, wi) {
+            Some(half) => half,
+            None => return color!(0),
+        };
+        let d = self.distribution.d(&wm);
+        let g = self.distribution.g(wo, wi);
+        let fresnel = fresnel_dielectric(wo.dot(&wm), self.eta);
+        let value = if reflect {
+            d * g * fresnel / (4.0 * wo.z)
+        } else {
+            let denominator = wi.dot(&wm) + wo.dot(&wm) / self.eta;
+            let denominator = denominator * denominator * wo.z;
+            d * (1.0 - fresnel) * g * (wi.dot(&wm) * wo.dot(&wm) / denominator).abs()
+        };
+        color!(value)
+    }
+
+    fn pdf(&self, wo: &Vec3, wi: &Vec3) -> f64 {
+        let (wm, reflect) = match self.half_vector(wo, wi) {
+            Some(half) => half,
+            None => return 0.0,
+        };
+        let fresnel = fresnel_dielectric(wo.dot(&wm), self.eta);
+        let visible = self.distribution.visible_d(wo, &wm);
+        if reflect {
+            visible / (4.0 * wo.dot(&wm).abs()) * fresnel
+        } else {
+            let denominator = wi.dot(&wm) + wo.dot(&wm) / self.eta;
+            let dwm_dwi = wi.dot(&wm).abs() / (denominator * denominator);
+            visible * dwm_dwi * (1.0 - fresnel)
+        }
+    }
+
+    // Reflects or refracts about a visible normal, in proportion to Fresnel.
+    fn sample(&self, wo: &Vec3) -> Vec3 {
+        let wm = self.distribution.sample_wm(wo);
+        let fresnel = fresnel_dielectric(wo.dot(&wm), self.eta);
+        let (wi, reflected) = if f64::random() < fresnel {
+            (reflect(wo, &wm), true)
+        } else {
+            match refract(wo, &wm, self.eta) {
+                Some(wi) => (wi, false),
+                None => (reflect(wo, &wm), true),
+            }
+        };
+        if (wi.z > 0.0) == reflected && wi.z != 0.0 {
+            wi
+        } else {
+            Vec3::default()
+        }
+    }
+}
+
+// Samples a microfacet lobe around a shading normal.
+pub struct LobePdf<L> {
+    frame: Onb,
+    wo: Vec3,
+    lobe: L,
+}
+
+impl<L: Lobe> LobePdf<L> {
+    // `wo` points back along the incoming ray.
+    pub fn new(normal: &Vec3, wo: &Vec3, lobe: L) -> Self {
+        let frame = Onb::new(normal);
+        let wo = frame.to_local(&wo.unit());
+        Self { frame, wo, lobe }
+    }
+
+    // BSDF times the cosine of `direction`, in world space.
+    pub fn eval(&self, direction: &Vec3) -> Color {
+        match self.to_local(direction) {
+            Some(wi) => self.lobe.eval(&self.wo, &wi),
+            None => color!(0),
+        }
+    }
+
+    fn to_local(&self, direction: &Vec3) -> Option<Vec3> {
+        if direction.len_squared() == 0.0 {
+            return None;
+        }
+        Some(self.frame.to_local(&direction.unit()))
+    }
+}
+
+impl<L: Lobe> Pdf for LobePdf<L> {
+    fn value(&self, direction: &Vec3) -> f64 {
+        match self.to_local(direction) {
+            Some(wi) => self.lobe.pdf(&self.wo, &wi),
+            None => 0.0,
+        }
+    }
+
+    fn generate(&self) -> Vec3 {
+        self.frame.local(&self.lobe.sample(&self.wo))
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Complex {
+    re: f64,
+    im: f64,
+}
+
+impl Complex {
+    fn new(re: f64, im: f64) -> Self {
+        Self { re, im }
+    }
+
+    fn norm(&self) -> f64 {
+        self.re * self.re + self.im * self.im
+    }
+
+    fn sqrt(self) -> Self {
+        let n = self.norm().sqrt();
+        if n == 0.0 {
+            return Self::new(0.0, 0.0);
+        }
+        let t1 = (0.5 * (n + self.re.abs())).sqrt();
+        let t2 = 0.5 * self.im / t1;
+        if self.re >= 0.0 {
+            Self::new(t1, t2)
+        } else {
+            Self::new(t2.abs(), t1.copysign(self.im))
+        }
+    }
+}
+
+impl ops::Add for Complex {
+    type Output = Self;
+
+    fn add(self, rhs: Self) -> Self {
+        Self::new(self.re + rhs.re, self.im + rhs.im)
+    }
+}
+
+impl ops::Sub for Complex {
+    type Output = Self;
+
+    fn sub(self, rhs: Self) -> Self {
+        Self::new(self.re - rhs.re, self.im - rhs.im)
+    }
+}
+
+impl ops::Mul for Complex {
+    type Output = Self;
+
+    fn mul(self, rhs: Self) -> Self {
+        Self::new(
+            self.re * rhs.re - self.im * rhs.im,
+            self.re * rhs.im + self.im * rhs.re,
+        )
+    }
+}
+
+impl ops::Div for Complex {
+    type Output = Self;
+
+    fn div(self, rhs: Self) -> Self {
+        let scale = 1.0 / rhs.norm();
+        Self::new(
+            scale * (self.re * rhs.re + self.im * rhs.im),
+            scale * (self.im * rhs.re - self.re * rhs.im),
+        )
+    }
+}
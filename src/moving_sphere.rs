@@ -0,0 +1,79 @@
+use std::{f64::consts::PI, sync::Arc};
+
+use crate::{
+    aabb::Aabb,
+    hit::{Hit, HitRecord},
+    material::Material,
+    ray::Ray,
+    sphere::{hit_sphere, sphere_pdf_value, sphere_random_direction},
+    v3,
+    vec3::{Point, Vec3},
+};
+
+pub struct MovingSphere {
+    center0: Point,
+    center1: Point,
+    time0: f64,
+    time1: f64,
+    radius: f64,
+    material: Arc<Material>,
+}
+
+impl MovingSphere {
+    pub fn new(
+        center0: Point,
+        center1: Point,
+        time0: f64,
+        time1: f64,
+        radius: f64,
+        material: Arc<Material>,
+    ) -> Box<Self> {
+        Box::new(Self {
+            center0,
+            center1,
+            time0,
+            time1,
+            radius,
+            material,
+        })
+    }
+
+    pub fn center(&self, time: f64) -> Point {
+        if self.time1 <= self.time0 {
+            return self.center0;
+        }
+        // Stays put outside the shutter interval, within the bounding box.
+        let t = ((time - self.time0) / (self.time1 - self.time0)).clamp(0.0, 1.0);
+        self.center0 + t * (self.center1 - self.center0)
+    }
+}
+
+impl Hit for MovingSphere {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        let center = self.center(ray.time);
+        hit_sphere(center, self.radius, &*self.material, ray, t_min, t_max)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let r = v3!(self.radius.abs());
+        let box0 = Aabb::new(self.center0 - r, self.center0 + r);
+        let box1 = Aabb::new(self.center1 - r, self.center1 + r);
+        Some(box0.surrounding(&box1))
+    }
+
+    fn pdf_value(&self, origin: &Point, direction: &Vec3, time: f64) -> f64 {
+        sphere_pdf_value(self.center(time), self.radius, origin, direction)
+    }
+
+    fn random_direction(&self, origin: &Point, time: f64) -> Option<Vec3> {
+        Some(sphere_random_direction(
+            self.center(time),
+            self.radius,
+            origin,
+        ))
+    }
+
+    fn area(&self) -> f64 {
+        4.0 * PI * self.radius * self.radius
+    }
+}
@@ -0,0 +1,526 @@
+use std::{
+    collections::HashMap,
+    error, fmt, fs, io,
+    path::{Path, PathBuf},
+    str::SplitWhitespace,
+    sync::Arc,
+};
+
+use crate::{
+    color,
+    hit::HitList,
+    material::Material,
+    mesh::{MeshData, MeshError, TriangleMesh},
+    v3,
+    vec3::{Color, Point, Vec3},
+};
+
+#[derive(Debug)]
+pub enum ObjError {
+    Io(PathBuf, io::Error),
+    Parse {
+        path: PathBuf,
+        line: usize,
+        message: String,
+    },
+    Mesh {
+        path: PathBuf,
+        group: String,
+        err: MeshError,
+    },
+}
+
+impl fmt::Display for ObjError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(path, err) => write!(f, "{}: {}", path.display(), err),
+            Self::Parse {
+                path,
+                line,
+                message,
+            } => write!(f, "{}:{}: {}", path.display(), line, message),
+            Self::Mesh { path, group, err } => {
+                write!(f, "{}: group {}: {}", path.display(), group, err)
+            }
+        }
+    }
+}
+
+impl error::Error for ObjError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Self::Io(_, err) => Some(err),
+            Self::Parse { .. } => None,
+            Self::Mesh { err, .. } => Some(err),
+        }
+    }
+}
+
+pub struct ObjGroup {
+    pub name: String,
+    pub mesh: Box<TriangleMesh>,
+}
+
+pub struct Obj {
+    pub groups: Vec<ObjGroup>,
+}
+
+impl Obj {
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, ObjError> {
+        let path = path.as_ref();
+        let source = read(path)?;
+        let mut parser = Parser::new(path);
+        for (i, line) in source.lines().enumerate() {
+            parser.line = i + 1;
+            parser.parse_line(line)?;
+        }
+        parser.finish_group()?;
+        Ok(Self {
+            groups: parser.groups,
+        })
+    }
+
+    pub fn into_hit_list(self) -> HitList {
+        let mut list = HitList::new();
+        for group in self.groups {
+            list.add(group.mesh);
+        }
+        list
+    }
+}
+
+pub fn load_mtl<P: AsRef<Path>>(path: P) -> Result<HashMap<String, Arc<Material>>, ObjError> {
+    let path = path.as_ref();
+    let source = read(path)?;
+    let mut materials = HashMap::new();
+    let mut current: Option<(String, Mtl)> = None;
+
+    for (i, line) in source.lines().enumerate() {
+        let err = |message: String| ObjError::Parse {
+            path: path.to_path_buf(),
+            line: i + 1,
+            message,
+        };
+        let line = strip_comment(line);
+        let mut tokens = line.split_whitespace();
+        let keyword = match tokens.next() {
+            Some(keyword) => keyword,
+            None => continue,
+        };
+
+        if keyword == "newmtl" {
+            let name = tokens.collect::<Vec<_>>().join(" ");
+            if name.is_empty() {
+                return Err(err("newmtl without a name".to_string()));
+            }
+            if let Some((name, mtl)) = current.take() {
+                materials.insert(name, mtl.to_material());
+            }
+            current = Some((name, Mtl::default()));
+            continue;
+        }
+
+        let mtl = match current.as_mut() {
+            Some((_, mtl)) => mtl,
+            None if keyword.starts_with("map_") => continue,
+            None => return Err(err(format!("'{}' before newmtl", keyword))),
+        };
+        match keyword {
+            "Kd" => mtl.diffuse = parse_color(&mut tokens).map_err(err)?,
+            "Ks" => mtl.specular = parse_color(&mut tokens).map_err(err)?,
+            "Ns" => mtl.shininess = parse_f64(tokens.next()).map_err(err)?,
+            "Ni" => mtl.refraction_index = parse_f64(tokens.next()).map_err(err)?,
+            "d" => mtl.dissolve = parse_f64(tokens.next()).map_err(err)?,
+            "Tr" => mtl.dissolve = 1.0 - parse_f64(tokens.next()).map_err(err)?,
+            "illum" => {
+                mtl.illum = tokens
+                    .next()
+                    .and_then(|t| t.parse().ok())
+                    .ok_or_else(|| err("invalid illumination model".to_string()))?
+            }
+            _ => {}
+        }
+    }
+    if let Some((name, mtl)) = current {
+        materials.insert(name, mtl.to_material());
+    }
+
+    Ok(materials)
+}
+
+struct Mtl {
+    diffuse: Color,
+    specular: Color,
+    shininess: f64,
+    refraction_index: f64,
+    dissolve: f64,
+    illum: u32,
+}
+
+impl Default for Mtl {
+    fn default() -> Self {
+        Self {
+            diffuse: color!(0.8),
+            specular: color!(0),
+            shininess: 0.0,
+            refraction_index: 1.5,
+            dissolve: 1.0,
+            illum: 2,
+        }
+    }
+}
+
+impl Mtl {
+    fn to_material(&self) -> Arc<Material> {
+        let is_transparent = self.dissolve < 1.0 || matches!(self.illum, 4 | 6 | 7 | 9);
+        let is_mirror = matches!(self.illum, 3 | 5)
+            || (self.diffuse.is_near_zero() && !self.specular.is_near_zero());
+
+        if is_transparent {
+            Material::new_dielectric(self.refraction_index)
+        } else if is_mirror {
+            // Maps the Phong exponent onto a roughness the same way as Beckmann.
+            let fuzz = (2.0 / (self.shininess + 2.0)).sqrt();
+            Material::new_metal(self.specular, fuzz)
+        } else {
+            Material::new_lambertian(self.diffuse)
+        }
+    }
+}
+
+#[derive(Default)]
+struct Group {
+    name: String,
+    positions: Vec<Point>,
+    normals: Vec<Option<Vec3>>,
+    uvs: Vec<Option<(f64, f64)>>,
+    indices: Vec<[usize; 3]>,
+    material_ids: Vec<usize>,
+    materials: Vec<Arc<Material>>,
+    vertices: HashMap<(usize, Option<usize>, Option<usize>), usize>,
+}
+
+struct Parser<'a> {
+    path: &'a Path,
+    line: usize,
+    positions: Vec<Point>,
+    normals: Vec<Vec3>,
+    uvs: Vec<(f64, f64)>,
+    library: HashMap<String, Arc<Material>>,
+    material: Arc<Material>,
+    group: Group,
+    groups: Vec<ObjGroup>,
+}
+
+impl<'a> Parser<'a> {
+    fn new(path: &'a Path) -> Self {
+        Self {
+            path,
+            line: 0,
+            positions: Vec::new(),
+            normals: Vec::new(),
+            uvs: Vec::new(),
+            library: HashMap::new(),
+            material: Material::new_lambertian(color!(0.8)),
+            group: Group {
+                name: "default".to_string(),
+                ..Group::default()
+            },
+            groups: Vec::new(),
+        }
+    }
+
+    fn error(&self, message: String) -> ObjError {
+        ObjError::Parse {
+            path: self.path.to_path_buf(),
+            line: self.line,
+            message,
+        }
+    }
+
+    fn parse_line(&mut self, line: &str) -> Result<(), ObjError> {
+        let line = strip_comment(line);
+        let mut tokens = line.split_whitespace();
+        let keyword = match tokens.next() {
+            Some(keyword) => keyword,
+            None => return Ok(()),
+        };
+
+        match keyword {
+            "v" => {
+                let p = parse_vec3(&mut tokens).map_err(|m| self.error(m))?;
+                self.positions.push(p);
+            }
+            "vn" => {
+                let n = parse_vec3(&mut tokens).map_err(|m| self.error(m))?;
+                self.normals.push(n);
+            }
+            "vt" => {
+                let u = parse_f64(tokens.next()).map_err(|m| self.error(m))?;
+                let v = match tokens.next() {
+                    Some(v) => parse_f64(Some(v)).map_err(|m| self.error(m))?,
+                    None => 0.0,
+                };
+                self.uvs.push((u, v));
+            }
+            "f" => self.parse_face(tokens)?,
+            "g" | "o" => {
+                self.finish_group()?;
+                let name = tokens.collect::<Vec<_>>().join(" ");
+                self.group.name = if name.is_empty() {
+                    "default".to_string()
+                } else {
+                    name
+                };
+            }
+            "mtllib" => {
+                let dir = self.path.parent().unwrap_or_else(|| Path::new(""));
+                for file in tokens {
+                    let library = load_mtl(dir.join(file))?;
+                    self.library.extend(library);
+                }
+            }
+            "usemtl" => {
+                let name = tokens.collect::<Vec<_>>().join(" ");
+                let material = self
+                    .library
+                    .get(&name)
+                    .cloned()
+                    .ok_or_else(|| self.error(format!("unknown material '{}'", name)))?;
+                self.material = material;
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn parse_face(&mut self, tokens: SplitWhitespace) -> Result<(), ObjError> {
+        let mut polygon = Vec::new();
+        for token in tokens {
+            let mut parts = token.split('/');
+            let v = self.parse_index(parts.next(), self.positions.len(), "vertex")?;
+            let vt = match parts.next() {
+                Some("") | None => None,
+                index => Some(self.parse_index(index, self.uvs.len(), "texture")?),
+            };
+            let vn = match parts.next() {
+                Some("") | None => None,
+                index => Some(self.parse_index(index, self.normals.len(), "normal")?),
+            };
+            polygon.push(self.vertex(v, vt, vn));
+        }
+        if polygon.len() < 3 {
+            return Err(self.error(format!(
+                "face with {} vertices, expected at least 3",
+                polygon.len()
+            )));
+        }
+
+        let material_id = self.material_id();
+        for i in 1..polygon.len() - 1 {
+            self.group
+                .indices
+                .push([polygon[0], polygon[i], polygon[i + 1]]);
+            self.group.material_ids.push(material_id);
+        }
+        Ok(())
+    }
+
+    fn parse_index(&self, token: Option<&str>, len: usize, kind: &str) -> Result<usize, ObjError> {
+        let token = token.unwrap_or("");
+        let index: i64 = token
+            .parse()
+            .map_err(|_| self.error(format!("invalid {} index '{}'", kind, token)))?;
+        let resolved = if index < 0 {
+            len as i64 + index
+        } else {
+            index - 1
+        };
+        if resolved < 0 || resolved >= len as i64 {
+            return Err(self.error(format!("{} index {} out of range", kind, index)));
+        }
+        Ok(resolved as usize)
+    }
+
+    fn vertex(&mut self, v: usize, vt: Option<usize>, vn: Option<usize>) -> usize {
+        let (positions, uvs, normals) = (&self.positions, &self.uvs, &self.normals);
+        let group = &mut self.group;
+        if let Some(&index) = group.vertices.get(&(v, vt, vn)) {
+            return index;
+        }
+        let index = group.positions.len();
+        group.positions.push(positions[v]);
+        group.uvs.push(vt.map(|vt| uvs[vt]));
+        group.normals.push(vn.map(|vn| normals[vn]));
+        group.vertices.insert((v, vt, vn), index);
+        index
+    }
+
+    fn material_id(&mut self) -> usize {
+        let material = &self.material;
+        let group = &mut self.group;
+        match group
+            .materials
+            .iter()
+            .position(|m| Arc::ptr_eq(m, material))
+        {
+            Some(id) => id,
+            None => {
+                group.materials.push(material.clone());
+                group.materials.len() - 1
+            }
+        }
+    }
+
+    fn finish_group(&mut self) -> Result<(), ObjError> {
+        let group = std::mem::take(&mut self.group);
+        if group.indices.is_empty() {
+            return Ok(());
+        }
+
+        // Attributes are kept only when every vertex of the group carries them.
+        let normals = group.normals.iter().copied().collect::<Option<Vec<_>>>();
+        let uvs = group.uvs.iter().copied().collect::<Option<Vec<_>>>();
+        let data = MeshData {
+            positions: group.positions,
+            normals: normals.unwrap_or_default(),
+            uvs: uvs.unwrap_or_default(),
+            indices: group.indices,
+            material_ids: group.material_ids,
+        };
+        match TriangleMesh::with_materials(data, group.materials) {
+            Ok(mesh) => {
+                self.groups.push(ObjGroup {
+                    name: group.name,
+                    mesh,
+                });
+                Ok(())
+            }
+            Err(err) => Err(ObjError::Mesh {
+                path: self.path.to_path_buf(),
+                group: group.name,
+                err,
+            }),
+        }
+    }
+}
+
+fn read(path: &Path) -> Result<String, ObjError> {
+    fs::read_to_string(path).map_err(|err| ObjError::Io(path.to_path_buf(), err))
+}
+
+fn strip_comment(line: &str) -> &str {
+    match line.find('#') {
+        Some(i) => &line[..i],
+        None => line,
+    }
+}
+
+fn parse_f64(token: Option<&str>) -> Result<f64, String> {
+    let token = token.ok_or_else(|| "missing number".to_string())?;
+    token
+        .parse()
+        .map_err(|_| format!("invalid number '{}'", token))
+}
+
+fn parse_vec3(tokens: &mut SplitWhitespace) -> Result<Vec3, String> {
+    let x = parse_f64(tokens.next())?;
+    let y = parse_f64(tokens.next())?;
+    let z = parse_f64(tokens.next())?;
+    Ok(v3!(x, y, z))
+}
+
+fn parse_color(tokens: &mut SplitWhitespace) -> Result<Color, String> {
+    let r = parse_f64(tokens.next())?;
+    match (tokens.next(), tokens.next()) {
+        (None, _) => Ok(color!(r)),
+        (g, b) => Ok(color!(r, parse_f64(g)?, parse_f64(b)?)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write(name: &str, contents: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("raytracing-rs-obj-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(name);
+        fs::write(&path, contents).unwrap();
+        path
+    }
+
+    fn error_line<T>(result: Result<T, ObjError>) -> usize {
+        match result {
+            Err(ObjError::Parse { line, .. }) => line,
+            Err(err) => panic!("expected a parse error, got {}", err),
+            Ok(_) => panic!("expected a parse error"),
+        }
+    }
+
+    #[test]
+    fn loads_groups_and_materials() {
+        let library = write(
+            "quad.mtl",
+            "newmtl red # comment\nKd 1 0 0\nNs 10\nd 0.5\n\nnewmtl grey\nKd 0.5\n",
+        );
+        let path = write(
+            "quad.obj",
+            "mtllib quad.mtl\n\
+             v 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\n\
+             vt 0 0\nvt 1 0\nvt 1 1\nvt 0 1\n\
+             g front\nusemtl red\nf 1/1 2/2 3/3 4/4\n\
+             g back\nusemtl grey\nf -1 -2 -3\n",
+        );
+        let obj = Obj::load(path).unwrap();
+        let groups: Vec<_> = obj
+            .groups
+            .iter()
+            .map(|group| (group.name.as_str(), group.mesh.triangle_count()))
+            .collect();
+        assert_eq!(groups, [("front", 2), ("back", 1)]);
+
+        let materials = load_mtl(library).unwrap();
+        let mut names: Vec<_> = materials.keys().map(String::as_str).collect();
+        names.sort_unstable();
+        assert_eq!(names, ["grey", "red"]);
+    }
+
+    #[test]
+    fn rejects_malformed_faces() {
+        let vertices = "v 0 0 0\nv 1 0 0\nv 0 1 0\n";
+        for (name, face, line) in [
+            ("short.obj", "f 1 2\n", 4),
+            ("range.obj", "f 1 2 4\n", 4),
+            ("zero.obj", "f 0 1 2\n", 4),
+            ("texture.obj", "f 1/1 2/1 3/1\n", 4),
+            ("word.obj", "f 1 two 3\n", 4),
+        ] {
+            let path = write(name, &format!("{}{}", vertices, face));
+            assert_eq!(error_line(Obj::load(path)), line, "{}", name);
+        }
+
+        let path = write("vertex.obj", "v 0 0\n");
+        assert_eq!(error_line(Obj::load(path)), 1);
+        let path = write("material.obj", "usemtl missing\n");
+        assert_eq!(error_line(Obj::load(path)), 1);
+        assert!(matches!(
+            Obj::load(write("missing.mtl.obj", "mtllib nowhere.mtl\n")),
+            Err(ObjError::Io(..))
+        ));
+    }
+
+    #[test]
+    fn rejects_malformed_materials() {
+        for (name, source, line) in [
+            ("before.mtl", "Kd 1 1 1\n", 1),
+            ("unnamed.mtl", "newmtl\n", 1),
+            ("number.mtl", "newmtl a\nNs shiny\n", 2),
+            ("color.mtl", "newmtl a\nKd 1 0\n", 2),
+            ("illum.mtl", "newmtl a\nillum -1\n", 2),
+        ] {
+            let path = write(name, source);
+            assert_eq!(error_line(load_mtl(path)), line, "{}", name);
+        }
+    }
+}
@@ -0,0 +1,30 @@
+use crate::{v3, vec3::Vec3};
+
+// Orthonormal basis with `w` along the given direction.
+pub struct Onb {
+    pub u: Vec3,
+    pub v: Vec3,
+    pub w: Vec3,
+}
+
+impl Onb {
+    pub fn new(w: &Vec3) -> Self {
+        let w = w.unit();
+        let a = if w.x.abs() > 0.9 {
+            v3!(0, 1, 0)
+        } else {
+            v3!(1, 0, 0)
+        };
+        let v = w.cross(&a).unit();
+        let u = w.cross(&v);
+        Self { u, v, w }
+    }
+
+    pub fn local(&self, a: &Vec3) -> Vec3 {
+        a.x * self.u + a.y * self.v + a.z * self.w
+    }
+
+    pub fn to_local(&self, a: &Vec3) -> Vec3 {
+        v3!(a.dot(&self.u), a.dot(&self.v), a.dot(&self.w))
+    }
+}
@@ -0,0 +1,119 @@
+use std::f64::consts::PI;
+
+use crate::{
+    hit::Hit,
+    onb::Onb,
+    random::Random,
+    vec3::{Point, Vec3},
+};
+
+// A distribution over directions, with densities in solid angle.
+pub trait Pdf {
+    fn value(&self, direction: &Vec3) -> f64;
+
+    fn generate(&self) -> Vec3;
+}
+
+pub struct CosinePdf {
+    uvw: Onb,
+}
+
+impl CosinePdf {
+    pub fn new(normal: &Vec3) -> Self {
+        Self {
+            uvw: Onb::new(normal),
+        }
+    }
+}
+
+impl Pdf for CosinePdf {
+    fn value(&self, direction: &Vec3) -> f64 {
+        let cosine = direction.unit().dot(&self.uvw.w);
+        if cosine > 0.0 {
+            cosine / PI
+        } else {
+            0.0
+        }
+    }
+
+    fn generate(&self) -> Vec3 {
+        self.uvw.local(&Vec3::random_cosine_direction())
+    }
+}
+
+// Directions from `origin` toward an object, see `Hit::pdf_value`.
+pub struct HitPdf<'a> {
+    object: &'a dyn Hit,
+    origin: Point,
+    time: f64,
+}
+
+impl<'a> HitPdf<'a> {
+    pub fn new(object: &'a dyn Hit, origin: Point, time: f64) -> Self {
+        Self {
+            object,
+            origin,
+            time,
+        }
+    }
+}
+
+impl<'a> Pdf for HitPdf<'a> {
+    fn value(&self, direction: &Vec3) -> f64 {
+        self.object.pdf_value(&self.origin, direction, self.time)
+    }
+
+    // The zero vector, which no density covers, when the object cannot be
+    // sampled.
+    fn generate(&self) -> Vec3 {
+        self.object
+            .random_direction(&self.origin, self.time)
+            .unwrap_or_default()
+    }
+}
+
+// Picks either distribution with equal probability.
+pub struct MixturePdf<'a> {
+    pdfs: [&'a dyn Pdf; 2],
+}
+
+impl<'a> MixturePdf<'a> {
+    pub fn new(p0: &'a dyn Pdf, p1: &'a dyn Pdf) -> Self {
+        Self { pdfs: [p0, p1] }
+    }
+}
+
+impl<'a> Pdf for MixturePdf<'a> {
+    fn value(&self, direction: &Vec3) -> f64 {
+        0.5 * self.pdfs[0].value(direction) + 0.5 * self.pdfs[1].value(direction)
+    }
+
+    fn generate(&self) -> Vec3 {
+        if f64::random() < 0.5 {
+            self.pdfs[0].generate()
+        } else {
+            self.pdfs[1].generate()
+        }
+    }
+}
+
+// Weight of a sample drawn with density `f_pdf` when another strategy could
+// have produced it with `g_pdf`.
+pub fn power_heuristic(f_pdf: f64, g_pdf: f64) -> f64 {
+    let f = f_pdf * f_pdf;
+    let g = g_pdf * g_pdf;
+    if f + g == 0.0 {
+        0.0
+    } else {
+        f / (f + g)
+    }
+}
+
+// Converts a uniform density over `area` into solid angle as seen from a
+// point `distance_squared` away, where `cosine` is taken at the surface.
+pub(crate) fn area_to_solid_angle(distance_squared: f64, cosine: f64, area: f64) -> f64 {
+    if cosine <= 0.0 || area <= 0.0 {
+        return 0.0;
+    }
+    distance_squared / (cosine * area)
+}
@@ -0,0 +1,131 @@
+use rand::{rngs::StdRng, seq::SliceRandom, Rng, SeedableRng};
+
+use crate::{
+    v3,
+    vec3::{Point, Vec3},
+};
+
+const POINT_COUNT: usize = 256;
+
+#[derive(Clone, Copy, Debug)]
+pub enum Smoothing {
+    Trilinear,
+    Hermite,
+}
+
+pub struct Perlin {
+    gradients: Vec<Vec3>,
+    perm_x: Vec<usize>,
+    perm_y: Vec<usize>,
+    perm_z: Vec<usize>,
+    smoothing: Smoothing,
+}
+
+impl Perlin {
+    pub fn new(seed: u64) -> Self {
+        Self::with_smoothing(seed, Smoothing::Hermite)
+    }
+
+    pub fn with_smoothing(seed: u64, smoothing: Smoothing) -> Self {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let gradients = (0..POINT_COUNT)
+            .map(|_| loop {
+                let v = v3!(
+                    rng.gen_range(-1.0..1.0),
+                    rng.gen_range(-1.0..1.0),
+                    rng.gen_range(-1.0..1.0)
+                );
+                let len_squared = v.len_squared();
+                if len_squared > 1e-6 && len_squared < 1.0 {
+                    return v.unit();
+                }
+            })
+            .collect();
+        let mut permute = || {
+            let mut p = (0..POINT_COUNT).collect::<Vec<_>>();
+            p.shuffle(&mut rng);
+            p
+        };
+        let perm_x = permute();
+        let perm_y = permute();
+        let perm_z = permute();
+
+        Self {
+            gradients,
+            perm_x,
+            perm_y,
+            perm_z,
+            smoothing,
+        }
+    }
+
+    // Gradient noise in roughly [-1, 1].
+    pub fn noise(&self, p: &Point) -> f64 {
+        let (fx, fy, fz) = (p.x.floor(), p.y.floor(), p.z.floor());
+        let (u, v, w) = (p.x - fx, p.y - fy, p.z - fz);
+        let (i, j, k) = (fx as i64, fy as i64, fz as i64);
+
+        let (uu, vv, ww) = match self.smoothing {
+            Smoothing::Trilinear => (u, v, w),
+            Smoothing::Hermite => (hermite(u), hermite(v), hermite(w)),
+        };
+
+        let mut accum = 0.0;
+        for di in 0..2 {
+            for dj in 0..2 {
+                for dk in 0..2 {
+                    let index = self.perm_x[wrap(i + di)]
+                        ^ self.perm_y[wrap(j + dj)]
+                        ^ self.perm_z[wrap(k + dk)];
+                    let (fi, fj, fk) = (di as f64, dj as f64, dk as f64);
+                    let weight = v3!(u - fi, v - fj, w - fk);
+                    accum += (fi * uu + (1.0 - fi) * (1.0 - uu))
+                        * (fj * vv + (1.0 - fj) * (1.0 - vv))
+                        * (fk * ww + (1.0 - fk) * (1.0 - ww))
+                        * self.gradients[index].dot(&weight);
+                }
+            }
+        }
+        accum
+    }
+
+    pub fn turbulence(&self, p: &Point, octaves: u32) -> f64 {
+        let mut accum = 0.0;
+        let mut p = *p;
+        let mut weight = 1.0;
+        for _ in 0..octaves {
+            accum += weight * self.noise(&p);
+            weight *= 0.5;
+            p = p * 2.0;
+        }
+        accum.abs()
+    }
+
+    // Fractional Brownian motion: each octave doubles the frequency and
+    // halves the amplitude.
+    pub fn fbm(&self, p: &Point, octaves: u32) -> f64 {
+        let mut accum = 0.0;
+        let mut p = *p;
+        let mut amplitude = 1.0;
+        let mut total = 0.0;
+        for _ in 0..octaves {
+            accum += amplitude * self.noise(&p);
+            total += amplitude;
+            amplitude *= 0.5;
+            p = p * 2.0;
+        }
+        if total > 0.0 {
+            accum / total
+        } else {
+            0.0
+        }
+    }
+}
+
+fn hermite(t: f64) -> f64 {
+    t * t * (3.0 - 2.0 * t)
+}
+
+fn wrap(i: i64) -> usize {
+    (i & (POINT_COUNT as i64 - 1)) as usize
+}
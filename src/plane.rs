@@ -0,0 +1,62 @@
+use std::sync::Arc;
+
+use crate::{
+    aabb::Aabb,
+    hit::{Hit, HitRecord},
+    material::Material,
+    ray::Ray,
+    v3,
+    vec3::{Point, Vec3},
+};
+
+// Unbounded, so it has no bounding box and has to live outside any BVH.
+pub struct Plane {
+    point: Point,
+    normal: Vec3,
+    tangent: Vec3,
+    bitangent: Vec3,
+    material: Arc<Material>,
+}
+
+impl Plane {
+    pub fn new(point: Point, normal: Vec3, material: Arc<Material>) -> Box<Self> {
+        let normal = normal.unit();
+        let helper = if normal.x.abs() > 0.9 {
+            v3!(0, 1, 0)
+        } else {
+            v3!(1, 0, 0)
+        };
+        let tangent = helper.cross(&normal).unit();
+        let bitangent = normal.cross(&tangent);
+        Box::new(Self {
+            point,
+            normal,
+            tangent,
+            bitangent,
+            material,
+        })
+    }
+}
+
+impl Hit for Plane {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        let denom = self.normal.dot(&ray.direction);
+        if denom.abs() < 1e-12 {
+            return None;
+        }
+        let t = (self.point - ray.origin).dot(&self.normal) / denom;
+        if !(t > t_min && t < t_max) {
+            return None;
+        }
+
+        let p = ray.at(t);
+        let mut rec = HitRecord::new(ray, p, t, self.normal, &*self.material);
+        rec.u = (p - self.point).dot(&self.tangent);
+        rec.v = (p - self.point).dot(&self.bitangent);
+        Some(rec)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        None
+    }
+}
@@ -0,0 +1,594 @@
+use std::{
+    collections::HashMap, error, fmt, fs, io, path::Path, str::SplitAsciiWhitespace, sync::Arc,
+};
+
+use crate::{
+    color,
+    material::Material,
+    mesh::{MeshData, MeshError, TriangleMesh},
+    point, v3,
+    vec3::Color,
+};
+
+#[derive(Debug)]
+pub enum PlyError {
+    Io(io::Error),
+    Header { line: usize, message: String },
+    Body(String),
+    Mesh(MeshError),
+}
+
+impl fmt::Display for PlyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(err) => write!(f, "{}", err),
+            Self::Header { line, message } => {
+                write!(f, "invalid header at line {}: {}", line, message)
+            }
+            Self::Body(message) => write!(f, "invalid body: {}", message),
+            Self::Mesh(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl error::Error for PlyError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Self::Io(err) => Some(err),
+            Self::Mesh(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for PlyError {
+    fn from(err: io::Error) -> Self {
+        Self::Io(err)
+    }
+}
+
+impl From<MeshError> for PlyError {
+    fn from(err: MeshError) -> Self {
+        Self::Mesh(err)
+    }
+}
+
+pub struct Ply {
+    pub data: MeshData,
+    pub colors: Vec<Color>,
+}
+
+impl Ply {
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, PlyError> {
+        Self::parse(&fs::read(path)?)
+    }
+
+    pub fn parse(bytes: &[u8]) -> Result<Self, PlyError> {
+        let (header, body) = Header::parse(bytes)?;
+        let mut source = match header.format {
+            Format::Ascii => {
+                let text = std::str::from_utf8(body)
+                    .map_err(|_| PlyError::Body("ascii body is not utf-8".to_string()))?;
+                Source::Ascii(text.split_ascii_whitespace())
+            }
+            Format::BinaryLittleEndian => Source::Binary {
+                bytes: body,
+                big_endian: false,
+            },
+            Format::BinaryBigEndian => Source::Binary {
+                bytes: body,
+                big_endian: true,
+            },
+        };
+
+        let mut ply = Self {
+            data: MeshData::default(),
+            colors: Vec::new(),
+        };
+        for element in header.elements.iter() {
+            match element.name.as_str() {
+                "vertex" => ply.read_vertices(element, &mut source)?,
+                "face" => ply.read_faces(element, &mut source)?,
+                _ => {
+                    for _ in 0..element.count {
+                        for property in element.properties.iter() {
+                            source.read_property(property)?;
+                        }
+                    }
+                }
+            }
+        }
+
+        let vertex_count = ply.data.positions.len();
+        if let Some(index) = ply
+            .data
+            .indices
+            .iter()
+            .flatten()
+            .find(|&&i| i >= vertex_count)
+        {
+            return Err(PlyError::Body(format!(
+                "vertex index {} out of range",
+                index
+            )));
+        }
+        Ok(ply)
+    }
+
+    pub fn into_mesh(self, material: Arc<Material>) -> Result<Box<TriangleMesh>, PlyError> {
+        Ok(TriangleMesh::new(self.data, material)?)
+    }
+
+    // Each face gets `material` of the average colour of its vertices, white
+    // without vertex colours. Colours are rounded to 8 bits so faces of the
+    // same colour share a material.
+    pub fn into_colored_mesh<F>(self, mut material: F) -> Result<Box<TriangleMesh>, PlyError>
+    where
+        F: FnMut(Color) -> Arc<Material>,
+    {
+        if self.colors.is_empty() {
+            return self.into_mesh(material(color!(1)));
+        }
+
+        let (mut data, colors) = (self.data, self.colors);
+        let mut materials = Vec::new();
+        let mut ids = HashMap::new();
+        data.material_ids = data
+            .indices
+            .iter()
+            .map(|face| {
+                let average = face.iter().fold(color!(0), |sum, &i| sum + colors[i]) / 3.0;
+                let key = [average.x, average.y, average.z]
+                    .map(|c| (c.clamp(0.0, 1.0) * 255.0).round() as u8);
+                *ids.entry(key).or_insert_with(|| {
+                    let [r, g, b] = key.map(|c| f64::from(c) / 255.0);
+                    materials.push(material(color!(r, g, b)));
+                    materials.len() - 1
+                })
+            })
+            .collect();
+        Ok(TriangleMesh::with_materials(data, materials)?)
+    }
+
+    fn read_vertices(&mut self, element: &Element, source: &mut Source) -> Result<(), PlyError> {
+        let find = |names: &[&str]| {
+            element
+                .properties
+                .iter()
+                .position(|p| names.contains(&p.name.as_str()))
+        };
+        let position = match (find(&["x"]), find(&["y"]), find(&["z"])) {
+            (Some(x), Some(y), Some(z)) => [x, y, z],
+            _ => return Err(PlyError::Body("vertex without x, y, z".to_string())),
+        };
+        let normal = match (find(&["nx"]), find(&["ny"]), find(&["nz"])) {
+            (Some(x), Some(y), Some(z)) => Some([x, y, z]),
+            _ => None,
+        };
+        let color = match (
+            find(&["red", "r", "diffuse_red"]),
+            find(&["green", "g", "diffuse_green"]),
+            find(&["blue", "b", "diffuse_blue"]),
+        ) {
+            (Some(r), Some(g), Some(b)) => Some([r, g, b]),
+            _ => None,
+        };
+        let uv = match (
+            find(&["u", "s", "texture_u", "texture_s"]),
+            find(&["v", "t", "texture_v", "texture_t"]),
+        ) {
+            (Some(u), Some(v)) => Some([u, v]),
+            _ => None,
+        };
+
+        let mut values = vec![0.0; element.properties.len()];
+        for _ in 0..element.count {
+            for (value, property) in values.iter_mut().zip(element.properties.iter()) {
+                *value = source.read_property(property)?;
+            }
+            let [x, y, z] = position;
+            self.data
+                .positions
+                .push(point!(values[x], values[y], values[z]));
+            if let Some([x, y, z]) = normal {
+                self.data.normals.push(v3!(values[x], values[y], values[z]));
+            }
+            if let Some([r, g, b]) = color {
+                let scale = |i: usize| match element.properties[i].kind {
+                    Kind::Scalar(Scalar::U8) => values[i] / 255.0,
+                    Kind::Scalar(Scalar::U16) => values[i] / 65535.0,
+                    _ => values[i],
+                };
+                self.colors.push(color!(scale(r), scale(g), scale(b)));
+            }
+            if let Some([u, v]) = uv {
+                self.data.uvs.push((values[u], values[v]));
+            }
+        }
+        Ok(())
+    }
+
+    fn read_faces(&mut self, element: &Element, source: &mut Source) -> Result<(), PlyError> {
+        let indices = element
+            .properties
+            .iter()
+            .position(|p| p.name == "vertex_indices" || p.name == "vertex_index")
+            .ok_or_else(|| PlyError::Body("face without vertex_indices".to_string()))?;
+        if let Kind::Scalar(_) = element.properties[indices].kind {
+            return Err(PlyError::Body("vertex_indices is not a list".to_string()));
+        }
+
+        let mut polygon = Vec::new();
+        for _ in 0..element.count {
+            for (i, property) in element.properties.iter().enumerate() {
+                match property.kind {
+                    Kind::List(count, item) if i == indices => {
+                        let len = source.read(count)? as usize;
+                        polygon.clear();
+                        for _ in 0..len {
+                            let index = source.read(item)?;
+                            if index < 0.0 {
+                                return Err(PlyError::Body(format!(
+                                    "negative vertex index {}",
+                                    index
+                                )));
+                            }
+                            polygon.push(index as usize);
+                        }
+                    }
+                    _ => {
+                        source.read_property(property)?;
+                    }
+                }
+            }
+            if polygon.len() < 3 {
+                return Err(PlyError::Body(format!(
+                    "face with {} vertices",
+                    polygon.len()
+                )));
+            }
+            for i in 1..polygon.len() - 1 {
+                self.data
+                    .indices
+                    .push([polygon[0], polygon[i], polygon[i + 1]]);
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Format {
+    Ascii,
+    BinaryLittleEndian,
+    BinaryBigEndian,
+}
+
+#[derive(Clone, Copy)]
+enum Scalar {
+    I8,
+    U8,
+    I16,
+    U16,
+    I32,
+    U32,
+    F32,
+    F64,
+}
+
+impl Scalar {
+    fn parse(name: &str) -> Option<Self> {
+        match name {
+            "char" | "int8" => Some(Self::I8),
+            "uchar" | "uint8" => Some(Self::U8),
+            "short" | "int16" => Some(Self::I16),
+            "ushort" | "uint16" => Some(Self::U16),
+            "int" | "int32" => Some(Self::I32),
+            "uint" | "uint32" => Some(Self::U32),
+            "float" | "float32" => Some(Self::F32),
+            "double" | "float64" => Some(Self::F64),
+            _ => None,
+        }
+    }
+
+    fn size(self) -> usize {
+        match self {
+            Self::I8 | Self::U8 => 1,
+            Self::I16 | Self::U16 => 2,
+            Self::I32 | Self::U32 | Self::F32 => 4,
+            Self::F64 => 8,
+        }
+    }
+}
+
+enum Kind {
+    Scalar(Scalar),
+    List(Scalar, Scalar),
+}
+
+struct Property {
+    name: String,
+    kind: Kind,
+}
+
+struct Element {
+    name: String,
+    count: usize,
+    properties: Vec<Property>,
+}
+
+struct Header {
+    format: Format,
+    elements: Vec<Element>,
+}
+
+impl Header {
+    fn parse(bytes: &[u8]) -> Result<(Self, &[u8]), PlyError> {
+        let mut format = None;
+        let mut elements: Vec<Element> = Vec::new();
+        let mut offset = 0;
+        let mut line = 0;
+
+        loop {
+            line += 1;
+            let err = |message: &str| PlyError::Header {
+                line,
+                message: message.to_string(),
+            };
+            let end = bytes[offset..]
+                .iter()
+                .position(|&b| b == b'\n')
+                .ok_or_else(|| err("missing end_header"))?;
+            let text = std::str::from_utf8(&bytes[offset..offset + end])
+                .map_err(|_| err("header is not utf-8"))?
+                .trim_end_matches('\r');
+            offset += end + 1;
+
+            let tokens = text.split_whitespace().collect::<Vec<_>>();
+            if line == 1 {
+                if tokens != ["ply"] {
+                    return Err(err("missing ply magic number"));
+                }
+                continue;
+            }
+            match tokens.as_slice() {
+                ["end_header"] => break,
+                [] | ["comment", ..] | ["obj_info", ..] => {}
+                ["format", kind, "1.0"] => {
+                    format = Some(match *kind {
+                        "ascii" => Format::Ascii,
+                        "binary_little_endian" => Format::BinaryLittleEndian,
+                        "binary_big_endian" => Format::BinaryBigEndian,
+                        _ => return Err(err("unknown format")),
+                    })
+                }
+                ["format", ..] => return Err(err("unsupported format")),
+                ["element", name, count] => elements.push(Element {
+                    name: name.to_string(),
+                    count: count.parse().map_err(|_| err("invalid element count"))?,
+                    properties: Vec::new(),
+                }),
+                ["property", "list", count, item, name] => {
+                    let count =
+                        Scalar::parse(count).ok_or_else(|| err("unknown list count type"))?;
+                    let item = Scalar::parse(item).ok_or_else(|| err("unknown list item type"))?;
+                    elements
+                        .last_mut()
+                        .ok_or_else(|| err("property before element"))?
+                        .properties
+                        .push(Property {
+                            name: name.to_string(),
+                            kind: Kind::List(count, item),
+                        });
+                }
+                ["property", scalar, name] => {
+                    let scalar =
+                        Scalar::parse(scalar).ok_or_else(|| err("unknown property type"))?;
+                    elements
+                        .last_mut()
+                        .ok_or_else(|| err("property before element"))?
+                        .properties
+                        .push(Property {
+                            name: name.to_string(),
+                            kind: Kind::Scalar(scalar),
+                        });
+                }
+                _ => return Err(err(&format!("unexpected '{}'", text))),
+            }
+        }
+
+        let format = format.ok_or(PlyError::Header {
+            line,
+            message: "missing format".to_string(),
+        })?;
+        Ok((Self { format, elements }, &bytes[offset..]))
+    }
+}
+
+enum Source<'a> {
+    Ascii(SplitAsciiWhitespace<'a>),
+    Binary { bytes: &'a [u8], big_endian: bool },
+}
+
+impl<'a> Source<'a> {
+    fn read_property(&mut self, property: &Property) -> Result<f64, PlyError> {
+        match property.kind {
+            Kind::Scalar(scalar) => self.read(scalar),
+            Kind::List(count, item) => {
+                let len = self.read(count)? as usize;
+                for _ in 0..len {
+                    self.read(item)?;
+                }
+                Ok(len as f64)
+            }
+        }
+    }
+
+    fn read(&mut self, scalar: Scalar) -> Result<f64, PlyError> {
+        match self {
+            Self::Ascii(tokens) => {
+                let token = tokens
+                    .next()
+                    .ok_or_else(|| PlyError::Body("unexpected end of data".to_string()))?;
+                token
+                    .parse()
+                    .map_err(|_| PlyError::Body(format!("invalid number '{}'", token)))
+            }
+            Self::Binary { bytes, big_endian } => {
+                let size = scalar.size();
+                if bytes.len() < size {
+                    return Err(PlyError::Body("unexpected end of data".to_string()));
+                }
+                let (head, rest) = bytes.split_at(size);
+                *bytes = rest;
+
+                let mut buf = [0; 8];
+                buf[..size].copy_from_slice(head);
+                if *big_endian {
+                    buf[..size].reverse();
+                }
+                Ok(match scalar {
+                    Scalar::I8 => f64::from(buf[0] as i8),
+                    Scalar::U8 => f64::from(buf[0]),
+                    Scalar::I16 => f64::from(i16::from_le_bytes([buf[0], buf[1]])),
+                    Scalar::U16 => f64::from(u16::from_le_bytes([buf[0], buf[1]])),
+                    Scalar::I32 => f64::from(i32::from_le_bytes([buf[0], buf[1], buf[2], buf[3]])),
+                    Scalar::U32 => f64::from(u32::from_le_bytes([buf[0], buf[1], buf[2], buf[3]])),
+                    Scalar::F32 => f64::from(f32::from_le_bytes([buf[0], buf[1], buf[2], buf[3]])),
+                    Scalar::F64 => f64::from_le_bytes(buf),
+                })
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const POSITIONS: [[f32; 3]; 4] = [
+        [0.0, 0.0, 0.0],
+        [1.0, 0.0, 0.0],
+        [1.0, 1.0, 0.5],
+        [0.0, 1.0, -2.25],
+    ];
+    const COLORS: [[u8; 3]; 4] = [[255, 0, 0], [0, 255, 0], [0, 0, 255], [51, 102, 153]];
+
+    fn header(format: &str) -> String {
+        format!(
+            "ply\r\nformat {} 1.0\ncomment a quad\n\
+             element vertex 4\nproperty float x\nproperty float y\nproperty float z\n\
+             property uchar red\nproperty uchar green\nproperty uchar blue\n\
+             element face 1\nproperty list uchar int vertex_indices\n\
+             element extra 1\nproperty ushort id\nend_header\n",
+            format
+        )
+    }
+
+    fn binary(format: &str, big_endian: bool) -> Vec<u8> {
+        let mut bytes = header(format).into_bytes();
+        for (position, color) in POSITIONS.iter().zip(COLORS.iter()) {
+            for &c in position {
+                bytes.extend(if big_endian {
+                    c.to_be_bytes()
+                } else {
+                    c.to_le_bytes()
+                });
+            }
+            bytes.extend(color);
+        }
+        bytes.push(4);
+        for i in 0..4i32 {
+            bytes.extend(if big_endian {
+                i.to_be_bytes()
+            } else {
+                i.to_le_bytes()
+            });
+        }
+        bytes.extend([0, 7]);
+        bytes
+    }
+
+    fn ascii() -> Vec<u8> {
+        let mut text = header("ascii");
+        for (p, c) in POSITIONS.iter().zip(COLORS.iter()) {
+            text += &format!("{} {} {} {} {} {}\n", p[0], p[1], p[2], c[0], c[1], c[2]);
+        }
+        text += "4 0 1 2 3\n7\n";
+        text.into_bytes()
+    }
+
+    fn check(ply: &Ply) {
+        let positions: Vec<_> = ply.data.positions.iter().map(|p| [p.x, p.y, p.z]).collect();
+        let expected: Vec<_> = POSITIONS.iter().map(|p| p.map(f64::from)).collect();
+        assert_eq!(positions, expected);
+        let colors: Vec<_> = ply.colors.iter().map(|c| [c.x, c.y, c.z]).collect();
+        let expected: Vec<_> = COLORS
+            .iter()
+            .map(|c| c.map(|c| f64::from(c) / 255.0))
+            .collect();
+        assert_eq!(colors, expected);
+        assert_eq!(ply.data.indices, [[0, 1, 2], [0, 2, 3]]);
+    }
+
+    #[test]
+    fn reads_every_format_alike() {
+        check(&Ply::parse(&ascii()).unwrap());
+        check(&Ply::parse(&binary("binary_little_endian", false)).unwrap());
+        check(&Ply::parse(&binary("binary_big_endian", true)).unwrap());
+    }
+
+    #[test]
+    fn endianness_matters() {
+        // Byte-swapped, the second vertex index lands far out of range.
+        let swapped = Ply::parse(&binary("binary_big_endian", false));
+        assert!(matches!(swapped, Err(PlyError::Body(_))));
+    }
+
+    #[test]
+    fn rejects_malformed_headers() {
+        let header_line = |bytes: &[u8]| match Ply::parse(bytes) {
+            Err(PlyError::Header { line, .. }) => line,
+            Err(err) => panic!("expected a header error, got {}", err),
+            Ok(_) => panic!("expected a header error"),
+        };
+        assert_eq!(header_line(b"plx\nformat ascii 1.0\nend_header\n"), 1);
+        assert_eq!(header_line(b"ply\nformat ascii 1.0\n"), 3);
+        assert_eq!(header_line(b"ply\nformat ascii 2.0\nend_header\n"), 2);
+        assert_eq!(header_line(b"ply\nformat utf8 1.0\nend_header\n"), 2);
+        assert_eq!(header_line(b"ply\nproperty float x\nend_header\n"), 2);
+        assert_eq!(
+            header_line(b"ply\nformat ascii 1.0\nelement vertex 1\nproperty half x\n"),
+            4
+        );
+        assert_eq!(header_line(b"ply\nelement vertex 0\nend_header\n"), 3);
+    }
+
+    #[test]
+    fn rejects_malformed_bodies() {
+        let body = |text: &str| {
+            let mut bytes = b"ply\nformat ascii 1.0\nelement vertex 3\n\
+                property float x\nproperty float y\nproperty float z\n\
+                element face 1\nproperty list uchar int vertex_indices\nend_header\n"
+                .to_vec();
+            bytes.extend(text.as_bytes());
+            Ply::parse(&bytes)
+        };
+        let vertices = "0 0 0 1 0 0 0 1 0 ";
+        assert!(body(&format!("{}3 0 1 2", vertices)).is_ok());
+        for face in ["3 0 1", "3 0 1 3", "3 0 -1 2", "2 0 1", "3 0 one 2"] {
+            assert!(
+                matches!(
+                    body(&format!("{}{}", vertices, face)),
+                    Err(PlyError::Body(_))
+                ),
+                "{}",
+                face
+            );
+        }
+
+        let mut truncated = binary("binary_little_endian", false);
+        truncated.truncate(truncated.len() - 3);
+        assert!(matches!(Ply::parse(&truncated), Err(PlyError::Body(_))));
+    }
+}
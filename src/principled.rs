@@ -0,0 +1,424 @@
+use std::{f64::consts::PI, sync::Arc};
+
+use crate::{
+    color,
+    hit::HitRecord,
+    material::{Bsdf, Scatter},
+    microfacet::{
+        fresnel_dielectric, fresnel_schlick, reflect, refract, DielectricLobe, Lobe, LobePdf,
+        TrowbridgeReitz,
+    },
+    pdf::Pdf,
+    random::Random,
+    ray::Ray,
+    texture::{SolidColor, Texture},
+    v3,
+    vec3::{Color, Vec3},
+};
+
+// The clearcoat is always sampled alongside the other lobes, so below this
+// it gets too narrow and stays slightly rough.
+const MIN_CLEARCOAT_ROUGHNESS: f64 = 0.05;
+
+// Reflectance of the clearcoat at normal incidence, an index of 1.5.
+const CLEARCOAT_F0: f64 = 0.04;
+
+// Parameters of the principled BSDF, each of which may vary over the surface.
+// Scalar parameters take the luminance of their texture, so values packed
+// into one channel need a `ChannelTexture`.
+pub struct Principled {
+    base_color: Arc<dyn Texture>,
+    metallic: Arc<dyn Texture>,
+    roughness: Arc<dyn Texture>,
+    specular: Arc<dyn Texture>,
+    clearcoat: Arc<dyn Texture>,
+    clearcoat_roughness: Arc<dyn Texture>,
+    sheen: Arc<dyn Texture>,
+    transmission: Arc<dyn Texture>,
+    refraction_index: f64,
+    emission: Option<Arc<dyn Texture>>,
+}
+
+impl Principled {
+    // A rough white plastic until told otherwise.
+    pub fn new(base_color: Color) -> Self {
+        Self::new_texture(SolidColor::new(base_color))
+    }
+
+    pub fn new_texture(base_color: Arc<dyn Texture>) -> Self {
+        Self {
+            base_color,
+            metallic: constant(0.0),
+            roughness: constant(0.5),
+            specular: constant(0.5),
+            clearcoat: constant(0.0),
+            clearcoat_roughness: constant(0.1),
+            sheen: constant(0.0),
+            transmission: constant(0.0),
+            refraction_index: 1.5,
+            emission: None,
+        }
+    }
+
+    pub fn with_metallic(self, metallic: f64) -> Self {
+        self.with_metallic_texture(constant(metallic))
+    }
+
+    pub fn with_metallic_texture(mut self, metallic: Arc<dyn Texture>) -> Self {
+        self.metallic = metallic;
+        self
+    }
+
+    pub fn with_roughness(self, roughness: f64) -> Self {
+        self.with_roughness_texture(constant(roughness))
+    }
+
+    pub fn with_roughness_texture(mut self, roughness: Arc<dyn Texture>) -> Self {
+        self.roughness = roughness;
+        self
+    }
+
+    // 0.5 is a reflectance of 4% at normal incidence, as for most
+    // dielectrics.
+    pub fn with_specular(self, specular: f64) -> Self {
+        self.with_specular_texture(constant(specular))
+    }
+
+    pub fn with_specular_texture(mut self, specular: Arc<dyn Texture>) -> Self {
+        self.specular = specular;
+        self
+    }
+
+    pub fn with_clearcoat(self, clearcoat: f64, roughness: f64) -> Self {
+        self.with_clearcoat_texture(constant(clearcoat), constant(roughness))
+    }
+
+    pub fn with_clearcoat_texture(
+        mut self,
+        clearcoat: Arc<dyn Texture>,
+        roughness: Arc<dyn Texture>,
+    ) -> Self {
+        self.clearcoat = clearcoat;
+        self.clearcoat_roughness = roughness;
+        self
+    }
+
+    pub fn with_sheen(self, sheen: f64) -> Self {
+        self.with_sheen_texture(constant(sheen))
+    }
+
+    pub fn with_sheen_texture(mut self, sheen: Arc<dyn Texture>) -> Self {
+        self.sheen = sheen;
+        self
+    }
+
+    pub fn with_transmission(self, transmission: f64) -> Self {
+        self.with_transmission_texture(constant(transmission))
+    }
+
+    pub fn with_transmission_texture(mut self, transmission: Arc<dyn Texture>) -> Self {
+        self.transmission = transmission;
+        self
+    }
+
+    pub fn with_refraction_index(mut self, refraction_index: f64) -> Self {
+        self.refraction_index = refraction_index;
+        self
+    }
+
+    // Radiance given off from both sides, on top of what is scattered.
+    pub fn with_emission(self, emission: Color) -> Self {
+        self.with_emission_texture(SolidColor::new(emission))
+    }
+
+    pub fn with_emission_texture(mut self, emission: Arc<dyn Texture>) -> Self {
+        self.emission = Some(emission);
+        self
+    }
+
+    // The BSDF at a hit, in the frame of its normal.
+    pub fn lobe(&self, record: &HitRecord) -> PrincipledLobe {
+        let scalar = |texture: &Arc<dyn Texture>| {
+            let value = texture.value(record.u, record.v, &record.point);
+            value.luminance().clamp(0.0, 1.0)
+        };
+        let base_color = self.base_color.value(record.u, record.v, &record.point);
+        let metallic = scalar(&self.metallic);
+        let transmission = scalar(&self.transmission);
+        let eta = if record.is_front_face {
+            self.refraction_index
+        } else {
+            1.0 / self.refraction_index
+        };
+        let roughness = scalar(&self.roughness);
+        let distribution = TrowbridgeReitz::new(roughness);
+
+        let dielectric_f0 = color!(0.08 * scalar(&self.specular));
+        PrincipledLobe {
+            base_color,
+            specular_f0: (1.0 - metallic) * dielectric_f0 + metallic * base_color,
+            diffuse: (1.0 - metallic) * (1.0 - transmission),
+            glass: (1.0 - metallic) * transmission,
+            roughness,
+            smooth: distribution.is_smooth(),
+            distribution,
+            glass_lobe: DielectricLobe { distribution, eta },
+            clearcoat: scalar(&self.clearcoat),
+            clearcoat_distribution: TrowbridgeReitz::new(
+                scalar(&self.clearcoat_roughness).max(MIN_CLEARCOAT_ROUGHNESS),
+            ),
+            sheen: scalar(&self.sheen),
+        }
+    }
+}
+
+impl Bsdf for Principled {
+    fn scatter(&self, ray_in: &Ray, record: &HitRecord) -> Option<Scatter> {
+        let lobe = self.lobe(record);
+        if lobe.smooth {
+            if let Some(scatter) = lobe.scatter_smooth(ray_in, record) {
+                return Some(scatter);
+            }
+        }
+        let wo = -ray_in.direction;
+        Some(Scatter::Sampled {
+            pdf: Box::new(LobePdf::new(&record.normal, &wo, lobe)),
+        })
+    }
+
+    fn eval(&self, ray_in: &Ray, record: &HitRecord, direction: &Vec3) -> Color {
+        let wo = -ray_in.direction;
+        LobePdf::new(&record.normal, &wo, self.lobe(record)).eval(direction)
+    }
+
+    // Smooth lobes are left out, as with `eval`.
+    fn pdf(&self, ray_in: &Ray, record: &HitRecord, scattered: &Vec3) -> f64 {
+        let wo = -ray_in.direction;
+        LobePdf::new(&record.normal, &wo, self.lobe(record)).value(scattered)
+    }
+
+    fn emitted(&self, record: &HitRecord) -> Color {
+        match &self.emission {
+            Some(emission) => emission.value(record.u, record.v, &record.point),
+            None => color!(0),
+        }
+    }
+}
+
+fn constant(value: f64) -> Arc<dyn Texture> {
+    SolidColor::new(color!(value))
+}
+
+// A diffuse base with sheen, specular reflection that turns metallic, rough
+// glass for transmission, and a clearcoat on top of it all.
+pub struct PrincipledLobe {
+    base_color: Color,
+    specular_f0: Color,
+    // Weights of the diffuse base and glass, the rest being metal.
+    diffuse: f64,
+    glass: f64,
+    roughness: f64,
+    // Whether the specular and glass lobes are mirror-like, in which case
+    // `Principled::scatter` follows them as specular rays and the rest of
+    // the lobe only covers the diffuse base and clearcoat.
+    smooth: bool,
+    distribution: TrowbridgeReitz,
+    glass_lobe: DielectricLobe,
+    clearcoat: f64,
+    clearcoat_distribution: TrowbridgeReitz,
+    sheen: f64,
+}
+
+impl PrincipledLobe {
+    // Light not reflected by the clearcoat on the way in reaches the layers
+    // below.
+    fn base_weight(&self, wo: &Vec3) -> f64 {
+        1.0 - self.clearcoat * fresnel_schlick(wo.z, &color!(CLEARCOAT_F0)).x
+    }
+
+    // Probabilities of sampling the diffuse, specular, glass and clearcoat
+    // lobes, roughly following how much each reflects.
+    fn lobe_weights(&self, wo: &Vec3) -> [f64; 4] {
+        let base = self.base_weight(wo);
+        let weights = [
+            base * self.diffuse * (self.base_color.luminance() + self.sheen),
+            base * (1.0 - self.glass) * (0.1 + self.specular_f0.luminance()),
+            base * self.glass,
+            0.25 * self.clearcoat,
+        ];
+        let total = weights.iter().sum::<f64>();
+        if total > 0.0 {
+            weights.map(|weight| weight / total)
+        } else {
+            [1.0, 0.0, 0.0, 0.0]
+        }
+    }
+
+    // Probabilities of sampling each lobe through `Lobe::sample`, with the
+    // chance of doing so at all. Smooth specular and glass lobes are left
+    // out.
+    fn sampled_weights(&self, wo: &Vec3) -> ([f64; 4], f64) {
+        let weights = self.lobe_weights(wo);
+        if !self.smooth {
+            return (weights, 1.0);
+        }
+        let [diffuse, _, _, clearcoat] = weights;
+        let sampled = diffuse + clearcoat;
+        if sampled > 0.0 {
+            ([diffuse / sampled, 0.0, 0.0, clearcoat / sampled], sampled)
+        } else {
+            ([0.0; 4], 0.0)
+        }
+    }
+
+    // Follows the mirror-like specular or glass lobe of a smooth surface,
+    // picked in proportion to `lobe_weights`, or returns `None` for the
+    // others to be sampled.
+    fn scatter_smooth(&self, ray_in: &Ray, record: &HitRecord) -> Option<Scatter> {
+        let unit_direction = ray_in.direction.unit();
+        let cosine = (-unit_direction).dot(&record.normal);
+        let [_, specular, glass, _] = self.lobe_weights(&v3!(0, 0, cosine));
+        let base = self.base_weight(&v3!(0, 0, cosine));
+        let reflected = unit_direction.reflect(&record.normal);
+
+        let u = f64::random();
+        let (direction, attenuation) = if u < specular {
+            let fresnel = fresnel_schlick(cosine, &self.specular_f0);
+            (reflected, base * (1.0 - self.glass) * fresnel / specular)
+        } else if u < specular + glass {
+            // Reflecting in proportion to Fresnel leaves only the tint.
+            let eta = self.glass_lobe.eta;
+            let refracted = if f64::random() < fresnel_dielectric(cosine, eta) {
+                None
+            } else {
+                refract(&-unit_direction, &record.normal, eta)
+            };
+            match refracted {
+                Some(refracted) => (refracted, base * self.base_color),
+                None => (reflected, color!(base)),
+            }
+        } else {
+            return None;
+        };
+        Some(Scatter::Specular {
+            ray: ray_in.spawn(record.point, direction),
+            attenuation,
+        })
+    }
+
+    // The hue of the base color, used to tint the sheen.
+    fn tint(&self) -> Color {
+        let luminance = self.base_color.luminance();
+        if luminance > 0.0 {
+            self.base_color / luminance
+        } else {
+            color!(1)
+        }
+    }
+
+    // Disney's diffuse, which darkens at grazing angles when smooth and
+    // gains a retro-reflective rim when rough.
+    fn eval_diffuse(&self, wo: &Vec3, wi: &Vec3, wh: &Vec3) -> Color {
+        let cos_d = wi.dot(wh);
+        let fd90 = 0.5 + 2.0 * self.roughness * cos_d * cos_d;
+        let schlick = |cosine: f64| 1.0 + (fd90 - 1.0) * (1.0 - cosine).powi(5);
+        // What the specular layer reflects never reaches the diffuse base.
+        let transmitted = color!(1) - fresnel_schlick(wo.z, &self.specular_f0);
+        let diffuse = transmitted * self.base_color / PI * schlick(wo.z) * schlick(wi.z);
+        let sheen_color = 0.5 * (color!(1) + self.tint());
+        let sheen = self.sheen * sheen_color * (1.0 - cos_d).powi(5);
+        (diffuse + sheen) * wi.z
+    }
+}
+
+// GGX reflection with Fresnel `f0` at normal incidence.
+fn eval_reflection(distribution: &TrowbridgeReitz, f0: &Color, wo: &Vec3, wi: &Vec3) -> Color {
+    let wm = (*wi + *wo).unit();
+    let fresnel = fresnel_schlick(wo.dot(&wm), f0);
+    distribution.d(&wm) * distribution.g(wo, wi) * fresnel / (4.0 * wo.z)
+}
+
+fn reflection_pdf(distribution: &TrowbridgeReitz, wo: &Vec3, wi: &Vec3) -> f64 {
+    let wm = (*wi + *wo).unit();
+    distribution.visible_d(wo, &wm) / (4.0 * wo.dot(&wm).abs())
+}
+
+impl Lobe for PrincipledLobe {
+    fn eval(&self, wo: &Vec3, wi: &Vec3) -> Color {
+        if wo.z <= 0.0 || wi.z == 0.0 {
+            return color!(0);
+        }
+        let base = self.base_weight(wo);
+        if self.smooth {
+            // Only reached the times `Principled::scatter` did not follow a
+            // mirror-like lobe, so scaled up to make up for the others.
+            let (_, sampled) = self.sampled_weights(wo);
+            let wh = *wi + *wo;
+            if sampled <= 0.0 || wi.z < 0.0 || wh.len_squared() == 0.0 {
+                return color!(0);
+            }
+            let diffuse = self.diffuse * self.eval_diffuse(wo, wi, &wh.unit());
+            let clearcoat = self.clearcoat
+                * eval_reflection(&self.clearcoat_distribution, &color!(CLEARCOAT_F0), wo, wi);
+            return (base * diffuse + clearcoat) / sampled;
+        }
+        if wi.z < 0.0 {
+            // Transmitted light takes on the base color.
+            return base * self.glass * self.base_color * self.glass_lobe.eval(wo, wi);
+        }
+
+        let wh = *wi + *wo;
+        if wh.len_squared() == 0.0 {
+            return color!(0);
+        }
+        let wh = wh.unit();
+        let diffuse = self.diffuse * self.eval_diffuse(wo, wi, &wh);
+        let specular =
+            (1.0 - self.glass) * eval_reflection(&self.distribution, &self.specular_f0, wo, wi);
+        let glass = self.glass * self.glass_lobe.eval(wo, wi);
+        let clearcoat = self.clearcoat
+            * eval_reflection(&self.clearcoat_distribution, &color!(CLEARCOAT_F0), wo, wi);
+        base * (diffuse + specular + glass) + clearcoat
+    }
+
+    fn pdf(&self, wo: &Vec3, wi: &Vec3) -> f64 {
+        if wo.z <= 0.0 || wi.z == 0.0 {
+            return 0.0;
+        }
+        let ([diffuse, specular, glass, clearcoat], _) = self.sampled_weights(wo);
+        let mut pdf = 0.0;
+        if wi.z > 0.0 && (*wi + *wo).len_squared() > 0.0 {
+            pdf += diffuse * wi.z / PI
+                + clearcoat * reflection_pdf(&self.clearcoat_distribution, wo, wi);
+        }
+        // Smooth distributions have no density to evaluate.
+        if self.smooth {
+            return pdf;
+        }
+        if wi.z > 0.0 && (*wi + *wo).len_squared() > 0.0 {
+            pdf += specular * reflection_pdf(&self.distribution, wo, wi);
+        }
+        pdf + glass * self.glass_lobe.pdf(wo, wi)
+    }
+
+    fn sample(&self, wo: &Vec3) -> Vec3 {
+        let ([diffuse, specular, glass, _], _) = self.sampled_weights(wo);
+        let u = f64::random();
+        if u < diffuse {
+            return Vec3::random_cosine_direction();
+        }
+        let distribution = if u < diffuse + specular {
+            &self.distribution
+        } else if u < diffuse + specular + glass {
+            return self.glass_lobe.sample(wo);
+        } else {
+            &self.clearcoat_distribution
+        };
+        let wi = reflect(wo, &distribution.sample_wm(wo));
+        if wi.z > 0.0 {
+            wi
+        } else {
+            Vec3::default()
+        }
+    }
+}
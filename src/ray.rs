@@ -1,6 +1,12 @@
 use crate::{
+    background::{Background, Environment},
     color,
-    hit::Hit,
+    hit::{Hit, HitRecord},
+    light::{visible, AreaLight, Light, LightSample},
+    light_sampler::{LightLookup, LightSampler},
+    material::Scatter,
+    pdf::power_heuristic,
+    scene::Scene,
     v3,
     vec3::{Color, Point, Vec3},
 };
@@ -8,11 +14,33 @@ use crate::{
 pub struct Ray {
     pub origin: Point,
     pub direction: Vec3,
+    pub time: f64,
+    // In nanometres, once a dispersive medium has split the path by
+    // wavelength.
+    pub wavelength: Option<f64>,
 }
 
 impl Ray {
     pub fn new(origin: Point, direction: Vec3) -> Self {
-        Self { origin, direction }
+        Self::with_time(origin, direction, 0.0)
+    }
+
+    // A ray leaving at `time` within the camera's shutter interval.
+    pub fn with_time(origin: Point, direction: Vec3, time: f64) -> Self {
+        Self {
+            origin,
+            direction,
+            time,
+            wavelength: None,
+        }
+    }
+
+    // The next ray along the same path, keeping its time and wavelength.
+    pub fn spawn(&self, origin: Point, direction: Vec3) -> Self {
+        Self {
+            wavelength: self.wavelength,
+            ..Self::with_time(origin, direction, self.time)
+        }
     }
 
     pub fn at(&self, distance: f64) -> Point {
@@ -23,17 +51,305 @@ impl Ray {
     where
         H: Hit,
     {
+        self.to_color_with_background(world, &Background::default(), depth)
+    }
+
+    pub fn to_color_with_background<H>(
+        &self,
+        world: &H,
+        background: &Background,
+        depth: i32,
+    ) -> Color
+    where
+        H: Hit,
+    {
+        let environment = usize::from(matches!(background, Background::Environment(_)));
+        let integrator = Integrator {
+            world,
+            lights: &[],
+            background,
+            sampler: &LightSampler::Uniform(environment),
+            lookup: None,
+        };
+        integrator.radiance(self, depth, None)
+    }
+
+    // Diffuse bounces also sample directions toward `lights`, which only need
+    // their geometry; their materials are ignored. Unlike `to_color_in`, the
+    // environment and `lights` are picked with equal probability.
+    pub fn to_color_with_lights<H>(
+        &self,
+        world: &H,
+        lights: &dyn Hit,
+        background: &Background,
+        depth: i32,
+    ) -> Color
+    where
+        H: Hit,
+    {
+        let environment = usize::from(matches!(background, Background::Environment(_)));
+        let integrator = Integrator {
+            world,
+            lights: &[Box::new(AreaLight::borrowed(lights))],
+            background,
+            sampler: &LightSampler::Uniform(1 + environment),
+            lookup: None,
+        };
+        integrator.radiance(self, depth, None)
+    }
+
+    pub fn to_color_in(&self, scene: &Scene, depth: i32) -> Color {
+        let (sampler, lookup) = scene.sampling();
+        let integrator = Integrator {
+            world: scene.world(),
+            lights: scene.lights(),
+            background: scene.background(),
+            sampler,
+            lookup: Some(lookup),
+        };
+        integrator.radiance(self, depth, None)
+    }
+}
+
+// Path tracer with next event estimation. Diffuse vertices take one light
+// sample and one BSDF sample. An environment map counts as one more light,
+// after the others.
+struct Integrator<'a> {
+    world: &'a dyn Hit,
+    lights: &'a [Box<dyn Light + 'a>],
+    background: &'a Background,
+    sampler: &'a LightSampler,
+    // Without one, every light is checked for emitters that rays reach.
+    lookup: Option<&'a LightLookup>,
+}
+
+impl<'a> Integrator<'a> {
+    // `bsdf_pdf` is the density the previous vertex sampled `ray` with, or
+    // `None` for camera rays and specular bounces, which light sampling
+    // cannot produce.
+    fn radiance(&self, ray: &Ray, depth: i32, bsdf_pdf: Option<f64>) -> Color {
         if depth <= 0 {
             return color!(0, 0, 0);
         }
-        if let Some(rec) = world.hit(self, 0.001, f64::INFINITY) {
-            if let Some((ray_out, attenuation)) = rec.material.scatter(self, &rec) {
-                return attenuation * ray_out.to_color(world, depth - 1);
+        let rec = match self.world.hit(ray, 0.001, f64::INFINITY) {
+            Some(rec) => rec,
+            None => {
+                let environment = self.environment().map(|_| self.lights.len());
+                return self.weight(ray, bsdf_pdf, environment)
+                    * self.background.value(&ray.direction);
+            }
+        };
+
+        let emitted = rec.material.emitted(&rec);
+        let emitted = match bsdf_pdf {
+            Some(_) if !emitted.is_near_zero() => {
+                self.weight(ray, bsdf_pdf, self.emitter(ray, &rec)) * emitted
             }
+            _ => emitted,
+        };
+
+        match rec.material.scatter(ray, &rec) {
+            Some(Scatter::Specular {
+                ray: scattered,
+                attenuation,
+            }) => emitted + attenuation * self.radiance(&scattered, depth - 1, None),
+            Some(Scatter::Sampled { pdf }) => {
+                let mut color = emitted + self.direct(ray, &rec);
+
+                let direction = pdf.generate();
+                let pdf_value = pdf.value(&direction);
+                let f = rec.material.eval(ray, &rec, &direction);
+                if pdf_value > 0.0 && !f.is_near_zero() {
+                    let scattered = ray.spawn(rec.point, direction);
+                    let bsdf_pdf = rec.material.pdf(ray, &rec, &direction);
+                    color += f * self.radiance(&scattered, depth - 1, Some(bsdf_pdf)) / pdf_value;
+                }
+                color
+            }
+            None => emitted,
+        }
+    }
+
+    // One light sample. Hittable lights are weighted against BSDF sampling
+    // with the power heuristic; the rest can only be found this way. Both
+    // sides of the weighting take the BSDF density from `Bsdf::pdf`, so the
+    // weights still add up to one should it differ from the sampled one.
+    fn direct(&self, ray: &Ray, rec: &HitRecord) -> Color {
+        let (index, pmf) = match self.sampler.sample(&rec.point) {
+            Some(picked) => picked,
+            None => return color!(0),
+        };
+        let (sample, hittable) = match self.lights.get(index) {
+            Some(light) => (light.sample(&rec.point, ray.time), light.is_hittable()),
+            None => (self.environment_sample(), true),
+        };
+        let sample = match sample {
+            Some(sample) if sample.pdf > 0.0 => sample,
+            _ => return color!(0),
+        };
+
+        let f = rec.material.eval(ray, rec, &sample.direction);
+        if f.is_near_zero() {
             return color!(0);
         }
-        let unit_dir = self.direction.unit();
-        let t = 0.5 * (unit_dir.y + 1.0);
-        (1.0 - t) * color!(1, 1, 1) + t * color!(0.5, 0.7, 1)
+        let scattered = ray.spawn(rec.point, sample.direction);
+
+        if hittable {
+            // Only the picked light's own emission counts here, anything
+            // else the ray reaches is left to BSDF sampling.
+            let light_pdf = pmf * sample.pdf;
+            let bsdf_pdf = rec.material.pdf(ray, rec, &sample.direction);
+            let weight = power_heuristic(light_pdf, bsdf_pdf);
+            f * self.incoming(&scattered, sample.distance) * weight / light_pdf
+        } else if visible(self.world, &scattered, sample.distance) {
+            let pdf = sample.pdf * pmf;
+            f * sample.radiance / pdf
+        } else {
+            color!(0)
+        }
+    }
+
+    // Emission along a light sample, provided the first thing the ray
+    // meets is `distance` away.
+    fn incoming(&self, ray: &Ray, distance: f64) -> Color {
+        match self.world.hit(ray, 0.001, f64::INFINITY) {
+            Some(rec) if same_distance(rec.t * ray.direction.len(), distance) => {
+                rec.material.emitted(&rec)
+            }
+            None if distance == f64::INFINITY => self.background.value(&ray.direction),
+            _ => color!(0),
+        }
+    }
+
+    // The hittable light whose surface `ray` met at `rec`, if any.
+    fn emitter(&self, ray: &Ray, rec: &HitRecord) -> Option<usize> {
+        let distance = rec.t * ray.direction.len();
+        let reached = |index: usize| {
+            let light = self.lights[index].distance(&ray.origin, &ray.direction, ray.time);
+            matches!(light, Some(d) if same_distance(d, distance))
+        };
+        match self.lookup {
+            Some(lookup) => lookup.find(&rec.point, reached),
+            None => (0..self.lights.len()).find(|&index| reached(index)),
+        }
+    }
+
+    // `light` is the light the emission `ray` found belongs to, if any.
+    fn weight(&self, ray: &Ray, bsdf_pdf: Option<f64>, light: Option<usize>) -> f64 {
+        match bsdf_pdf {
+            Some(bsdf_pdf) => power_heuristic(
+                bsdf_pdf,
+                light.map_or(0.0, |index| self.light_pdf(ray, index)),
+            ),
+            None => 1.0,
+        }
+    }
+
+    // Density of `direct` picking light `index` and producing `ray`.
+    fn light_pdf(&self, ray: &Ray, index: usize) -> f64 {
+        let pdf = match self.lights.get(index) {
+            Some(light) => light.pdf(&ray.origin, &ray.direction, ray.time),
+            None => self.environment().map_or(0.0, |e| e.pdf(&ray.direction)),
+        };
+        if pdf > 0.0 {
+            self.sampler.pmf(&ray.origin, index) * pdf
+        } else {
+            0.0
+        }
+    }
+
+    fn environment_sample(&self) -> Option<LightSample> {
+        let (direction, radiance, pdf) = self.environment()?.sample();
+        Some(LightSample {
+            direction,
+            distance: f64::INFINITY,
+            radiance,
+            pdf,
+        })
+    }
+
+    fn environment(&self) -> Option<&Environment> {
+        match self.background {
+            Background::Environment(environment) => Some(environment),
+            _ => None,
+        }
+    }
+}
+
+// Whether two distances along a ray are the same hit, allowing for them
+// having been found by different intersection routines.
+fn same_distance(a: f64, b: f64) -> bool {
+    a.is_finite() && b.is_finite() && (a - b).abs() <= 1e-5 * a.max(b).max(1.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{f64::consts::PI, sync::Arc};
+
+    use super::*;
+    use crate::{
+        hit::HitList, light_sampler::LightSampling, material::Material, point, rect::XZRect,
+        texture::SolidColor,
+    };
+
+    const ALBEDO: f64 = 0.5;
+    const EMIT: f64 = 4.0;
+
+    // A diffuse floor under a two-sided 2x2 emitter one unit above the
+    // origin, with nothing else around.
+    fn scene(light_sampling: Option<LightSampling>) -> Scene {
+        let emitter = Material::new_diffuse_light_texture(SolidColor::new(color!(EMIT)), true);
+        let light: Arc<dyn Hit> =
+            Arc::from(XZRect::new(-1.0, 1.0, -1.0, 1.0, 1.0, emitter) as Box<dyn Hit>);
+        let mut world = HitList::new();
+        world.add(XZRect::new(
+            -10.0,
+            10.0,
+            -10.0,
+            10.0,
+            0.0,
+            Material::new_lambertian(color!(ALBEDO)),
+        ));
+        world.add(Box::new(light.clone()));
+
+        let mut scene = Scene::new(Box::new(world))
+            .with_background(Background::new_solid(color!(0)))
+            .with_light_sampling(light_sampling.unwrap_or_default());
+        if light_sampling.is_some() {
+            scene.add_light(AreaLight::new(light));
+        }
+        scene
+    }
+
+    // Form factor from a point to a parallel rectangle with a corner straight
+    // above it, `x` by `y` in units of its height.
+    fn corner_form_factor(x: f64, y: f64) -> f64 {
+        let (sx, sy) = ((1.0 + x * x).sqrt(), (1.0 + y * y).sqrt());
+        (x / sx * (y / sx).atan() + y / sy * (x / sy).atan()) / (2.0 * PI)
+    }
+
+    #[test]
+    fn direct_lighting_matches_the_form_factor() {
+        let expected = ALBEDO * EMIT * 4.0 * corner_form_factor(1.0, 1.0);
+        let ray = Ray::new(point!(-5, 0.5, 0), v3!(5, -0.5, 0));
+        for (light_sampling, samples) in [
+            (Some(LightSampling::Uniform), 20_000),
+            (Some(LightSampling::Power), 20_000),
+            (Some(LightSampling::Tree), 20_000),
+            (None, 100_000),
+        ] {
+            let scene = scene(light_sampling);
+            let total = (0..samples).fold(color!(0), |sum, _| sum + ray.to_color_in(&scene, 2));
+            let mean = total / f64::from(samples);
+            for value in [mean.x, mean.y, mean.z] {
+                assert!(
+                    (value - expected).abs() < 0.02 * expected,
+                    "{:?}: {} != {}",
+                    light_sampling,
+                    value,
+                    expected
+                );
+            }
+        }
     }
 }
@@ -0,0 +1,197 @@
+use std::sync::Arc;
+
+use crate::{
+    aabb::Aabb,
+    hit::{Hit, HitRecord},
+    material::Material,
+    pdf::area_to_solid_angle,
+    random::Random,
+    ray::Ray,
+    v3,
+    vec3::{Point, Vec3},
+};
+
+const THICKNESS: f64 = 1e-4;
+
+struct Rect {
+    // The two in-plane axes followed by the normal axis.
+    axes: [usize; 3],
+    a0: f64,
+    a1: f64,
+    b0: f64,
+    b1: f64,
+    k: f64,
+    material: Arc<Material>,
+}
+
+impl Rect {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        let [a, b, k] = self.axes;
+        let t = (self.k - ray.origin[k]) / ray.direction[k];
+        if !(t > t_min && t < t_max) {
+            return None;
+        }
+
+        let p = ray.at(t);
+        if p[a] < self.a0 || p[a] > self.a1 || p[b] < self.b0 || p[b] > self.b1 {
+            return None;
+        }
+
+        let mut rec = HitRecord::new(ray, p, t, self.point(0.0, 0.0, 1.0), &*self.material);
+        rec.u = (p[a] - self.a0) / (self.a1 - self.a0);
+        rec.v = (p[b] - self.b0) / (self.b1 - self.b0);
+        Some(rec)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(Aabb::new(
+            self.point(self.a0, self.b0, self.k - THICKNESS),
+            self.point(self.a1, self.b1, self.k + THICKNESS),
+        ))
+    }
+
+    fn pdf_value(&self, origin: &Point, direction: &Vec3, time: f64) -> f64 {
+        let ray = Ray::with_time(*origin, *direction, time);
+        match self.hit(&ray, 0.001, f64::INFINITY) {
+            Some(rec) => {
+                let area = self.area();
+                let distance_squared = rec.t * rec.t * direction.len_squared();
+                let cosine = (direction[self.axes[2]] / direction.len()).abs();
+                area_to_solid_angle(distance_squared, cosine, area)
+            }
+            None => 0.0,
+        }
+    }
+
+    fn area(&self) -> f64 {
+        (self.a1 - self.a0) * (self.b1 - self.b0)
+    }
+
+    fn random_direction(&self, origin: &Point) -> Vec3 {
+        let a = f64::random_in(self.a0, self.a1);
+        let b = f64::random_in(self.b0, self.b1);
+        self.point(a, b, self.k) - *origin
+    }
+
+    fn point(&self, a: f64, b: f64, k: f64) -> Vec3 {
+        let mut p = [0.0; 3];
+        p[self.axes[0]] = a;
+        p[self.axes[1]] = b;
+        p[self.axes[2]] = k;
+        v3!(p[0], p[1], p[2])
+    }
+}
+
+pub struct XYRect(Rect);
+
+impl XYRect {
+    pub fn new(x0: f64, x1: f64, y0: f64, y1: f64, z: f64, material: Arc<Material>) -> Box<Self> {
+        Box::new(Self(Rect {
+            axes: [0, 1, 2],
+            a0: x0,
+            a1: x1,
+            b0: y0,
+            b1: y1,
+            k: z,
+            material,
+        }))
+    }
+}
+
+impl Hit for XYRect {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        self.0.hit(ray, t_min, t_max)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        self.0.bounding_box()
+    }
+
+    fn pdf_value(&self, origin: &Point, direction: &Vec3, time: f64) -> f64 {
+        self.0.pdf_value(origin, direction, time)
+    }
+
+    fn random_direction(&self, origin: &Point, _time: f64) -> Option<Vec3> {
+        Some(self.0.random_direction(origin))
+    }
+
+    fn area(&self) -> f64 {
+        self.0.area()
+    }
+}
+
+pub struct XZRect(Rect);
+
+impl XZRect {
+    pub fn new(x0: f64, x1: f64, z0: f64, z1: f64, y: f64, material: Arc<Material>) -> Box<Self> {
+        Box::new(Self(Rect {
+            axes: [0, 2, 1],
+            a0: x0,
+            a1: x1,
+            b0: z0,
+            b1: z1,
+            k: y,
+            material,
+        }))
+    }
+}
+
+impl Hit for XZRect {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        self.0.hit(ray, t_min, t_max)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        self.0.bounding_box()
+    }
+
+    fn pdf_value(&self, origin: &Point, direction: &Vec3, time: f64) -> f64 {
+        self.0.pdf_value(origin, direction, time)
+    }
+
+    fn random_direction(&self, origin: &Point, _time: f64) -> Option<Vec3> {
+        Some(self.0.random_direction(origin))
+    }
+
+    fn area(&self) -> f64 {
+        self.0.area()
+    }
+}
+
+pub struct YZRect(Rect);
+
+impl YZRect {
+    pub fn new(y0: f64, y1: f64, z0: f64, z1: f64, x: f64, material: Arc<Material>) -> Box<Self> {
+        Box::new(Self(Rect {
+            axes: [1, 2, 0],
+            a0: y0,
+            a1: y1,
+            b0: z0,
+            b1: z1,
+            k: x,
+            material,
+        }))
+    }
+}
+
+impl Hit for YZRect {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        self.0.hit(ray, t_min, t_max)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        self.0.bounding_box()
+    }
+
+    fn pdf_value(&self, origin: &Point, direction: &Vec3, time: f64) -> f64 {
+        self.0.pdf_value(origin, direction, time)
+    }
+
+    fn random_direction(&self, origin: &Point, _time: f64) -> Option<Vec3> {
+        Some(self.0.random_direction(origin))
+    }
+
+    fn area(&self) -> f64 {
+        self.0.area()
+    }
+}
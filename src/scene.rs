@@ -0,0 +1,146 @@
+use std::sync::{Arc, OnceLock};
+
+use crate::{
+    background::Background,
+    bvh::BvhNode,
+    color,
+    hit::{Hit, HitList},
+    light::{AreaLight, Light},
+    light_sampler::{LightInfo, LightLookup, LightSampler, LightSampling},
+    material::Material,
+    random::Random,
+    sphere::Sphere,
+    vec3::Color,
+};
+
+// Everything a ray needs to be shaded: the geometry, the emitters to sample
+// directly, and what lies beyond.
+pub struct Scene {
+    world: Box<dyn Hit>,
+    lights: Vec<Box<dyn Light>>,
+    background: Background,
+    light_sampling: LightSampling,
+    // Built from the fields above on first use, an environment map counting
+    // as one more light. Anything changing them must reset it.
+    sampling: OnceLock<(LightSampler, LightLookup)>,
+}
+
+impl Scene {
+    pub fn new(world: Box<dyn Hit>) -> Self {
+        Self {
+            world,
+            lights: Vec::new(),
+            background: Background::default(),
+            light_sampling: LightSampling::default(),
+            sampling: OnceLock::new(),
+        }
+    }
+
+    pub fn add_light(&mut self, light: Box<dyn Light>) {
+        self.lights.push(light);
+        self.sampling = OnceLock::new();
+    }
+
+    pub fn with_background(mut self, background: Background) -> Self {
+        self.set_background(background);
+        self
+    }
+
+    pub fn set_background(&mut self, background: Background) {
+        self.background = background;
+        self.sampling = OnceLock::new();
+    }
+
+    pub fn with_light_sampling(mut self, light_sampling: LightSampling) -> Self {
+        self.light_sampling = light_sampling;
+        self.sampling = OnceLock::new();
+        self
+    }
+
+    pub fn world(&self) -> &dyn Hit {
+        &*self.world
+    }
+
+    pub fn lights(&self) -> &[Box<dyn Light>] {
+        &self.lights
+    }
+
+    pub fn background(&self) -> &Background {
+        &self.background
+    }
+
+    pub fn light_sampling(&self) -> LightSampling {
+        self.light_sampling
+    }
+
+    pub(crate) fn sampling(&self) -> (&LightSampler, &LightLookup) {
+        let (sampler, lookup) = self.sampling.get_or_init(|| {
+            let world_radius = self
+                .world
+                .bounding_box()
+                .map_or(1.0, |bbox| 0.5 * (bbox.maximum - bbox.minimum).len());
+            let mut lights = self
+                .lights
+                .iter()
+                .map(|light| LightInfo {
+                    power: light.power(world_radius),
+                    bounds: light.bounds(),
+                    hittable: light.is_hittable(),
+                })
+                .collect::<Vec<_>>();
+            if let Background::Environment(environment) = &self.background {
+                lights.push(LightInfo {
+                    power: environment.power(world_radius),
+                    bounds: None,
+                    hittable: false,
+                });
+            }
+            (
+                LightSampler::new(self.light_sampling, &lights),
+                LightLookup::new(&lights),
+            )
+        });
+        (sampler, lookup)
+    }
+
+    pub fn cornell_box() -> Self {
+        let mut scene = Self::new(Box::new(HitList::cornell_box()))
+            .with_background(Background::new_solid(color!(0)));
+        scene.add_light(AreaLight::new(Arc::new(HitList::cornell_box_lights())));
+        scene
+    }
+
+    // `HitList::random_scene` at night, with about 5% of the small spheres
+    // glowing.
+    pub fn random_glowing_scene() -> Self {
+        let mut world = HitList::new();
+        let mut lights: Vec<Box<dyn Light>> = Vec::new();
+        for object in HitList::random_scene().into_objects() {
+            // The small spheres are the only objects less than a unit across.
+            let small = object
+                .bounding_box()
+                .filter(|bbox| (bbox.maximum - bbox.minimum).len() < 1.0);
+            match small {
+                Some(bbox) if f64::random() < 0.05 => {
+                    let radius = 0.5 * (bbox.maximum.x - bbox.minimum.x);
+                    let emit = Color::random_in(color!(0.5), color!(1)) * 4.0;
+                    let material = Material::new_diffuse_light(emit);
+                    let sphere: Arc<dyn Hit> =
+                        Arc::from(Sphere::new(bbox.centroid(), radius, material) as Box<dyn Hit>);
+                    world.add(Box::new(sphere.clone()));
+                    lights.push(AreaLight::new(sphere));
+                }
+                _ => world.add(object),
+            }
+        }
+
+        let mut scene = Self::new(BvhNode::new(world)).with_background(Background::new_gradient(
+            color!(0.005, 0.005, 0.01),
+            color!(0.02, 0.03, 0.06),
+        ));
+        for light in lights {
+            scene.add_light(light);
+        }
+        scene
+    }
+}
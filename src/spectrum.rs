@@ -0,0 +1,132 @@
+use std::sync::OnceLock;
+
+use crate::{
+    color,
+    random::Random,
+    vec3::{Color, Vec3},
+};
+
+// Visible range sampled by `sample_wavelength`, in nanometres.
+pub const MIN_WAVELENGTH: f64 = 380.0;
+pub const MAX_WAVELENGTH: f64 = 780.0;
+
+// Wavelength at which dispersive media report their nominal index, the sodium
+// D line.
+const NOMINAL_WAVELENGTH: f64 = 589.3;
+
+// How the index of refraction of a medium varies with wavelength.
+#[derive(Clone, Copy, Debug)]
+pub enum RefractiveIndex {
+    Constant(f64),
+    // n = a + b / λ², with λ in micrometres.
+    Cauchy { a: f64, b: f64 },
+    // n² = 1 + Σ b λ² / (λ² - c), with λ in micrometres.
+    Sellmeier { b: [f64; 3], c: [f64; 3] },
+}
+
+impl RefractiveIndex {
+    // Schott N-BK7, a common crown glass.
+    pub fn bk7() -> Self {
+        Self::Sellmeier {
+            b: [1.039_612_12, 0.231_792_344, 1.010_469_45],
+            c: [0.006_000_698_67, 0.020_017_914_4, 103.560_653],
+        }
+    }
+
+    // Schott N-SF11, a dense flint glass with strong dispersion.
+    pub fn sf11() -> Self {
+        Self::Sellmeier {
+            b: [1.737_596_95, 0.313_747_346, 1.898_781_01],
+            c: [0.013_188_707, 0.062_306_814_2, 155.236_29],
+        }
+    }
+
+    pub fn diamond() -> Self {
+        Self::Sellmeier {
+            b: [4.3356, 0.3306, 0.0],
+            c: [0.106 * 0.106, 0.175 * 0.175, 0.0],
+        }
+    }
+
+    // Index at `wavelength` nanometres.
+    pub fn at(&self, wavelength: f64) -> f64 {
+        let micrometres = wavelength / 1000.0;
+        let l2 = micrometres * micrometres;
+        match self {
+            Self::Constant(n) => *n,
+            Self::Cauchy { a, b } => a + b / l2,
+            Self::Sellmeier { b, c } => {
+                let sum = b.iter().zip(c).map(|(b, c)| b * l2 / (l2 - c)).sum::<f64>();
+                (1.0 + sum).sqrt()
+            }
+        }
+    }
+
+    pub fn nominal(&self) -> f64 {
+        self.at(NOMINAL_WAVELENGTH)
+    }
+
+    pub fn is_dispersive(&self) -> bool {
+        !matches!(self, Self::Constant(_))
+    }
+}
+
+impl From<f64> for RefractiveIndex {
+    fn from(n: f64) -> Self {
+        Self::Constant(n)
+    }
+}
+
+// Picks a wavelength uniformly over the visible range.
+pub fn sample_wavelength() -> f64 {
+    f64::random_in(MIN_WAVELENGTH, MAX_WAVELENGTH)
+}
+
+// Linear RGB carried by a path following a single wavelength picked by
+// `sample_wavelength`. Averaged over wavelengths it comes to white, so paths
+// that split off a wavelength still render neutral surfaces unchanged.
+// Spectral colours lie outside the sRGB gamut, so negative channels are
+// clipped before normalising, keeping path weights non-negative.
+pub fn wavelength_to_rgb(wavelength: f64) -> Color {
+    let scale = RGB_SCALE.get_or_init(|| {
+        // Integrated once so that each channel averages to one.
+        let steps = 4000;
+        let step = (MAX_WAVELENGTH - MIN_WAVELENGTH) / steps as f64;
+        let sum = (0..steps)
+            .map(|i| spectral_rgb(MIN_WAVELENGTH + (i as f64 + 0.5) * step))
+            .fold(color!(0), |sum, rgb| sum + rgb);
+        1.0 / (sum / steps as f64)
+    });
+    spectral_rgb(wavelength) * *scale
+}
+
+fn spectral_rgb(wavelength: f64) -> Color {
+    let rgb = xyz_to_rgb(cie_xyz(wavelength));
+    color!(rgb.x.max(0.0), rgb.y.max(0.0), rgb.z.max(0.0))
+}
+
+static RGB_SCALE: OnceLock<Color> = OnceLock::new();
+
+// CIE 1931 colour matching functions, using the multi-lobe Gaussian fit of
+// Wyman, Sloan and Shirley.
+fn cie_xyz(wavelength: f64) -> Vec3 {
+    let lobe = |mean: f64, below: f64, above: f64| {
+        let sigma = if wavelength < mean { below } else { above };
+        let t = (wavelength - mean) / sigma;
+        (-0.5 * t * t).exp()
+    };
+    let x = 1.056 * lobe(599.8, 37.9, 31.0) + 0.362 * lobe(442.0, 16.0, 26.7)
+        - 0.065 * lobe(501.1, 20.4, 26.2);
+    let y = 0.821 * lobe(568.8, 46.9, 40.5) + 0.286 * lobe(530.9, 16.3, 31.1);
+    let z = 1.217 * lobe(437.0, 11.8, 36.0) + 0.681 * lobe(459.0, 26.0, 13.8);
+    Vec3::new(x, y, z)
+}
+
+// To linear sRGB primaries.
+fn xyz_to_rgb(xyz: Vec3) -> Color {
+    color!(
+        3.2406 * xyz.x - 1.5372 * xyz.y - 0.4986 * xyz.z,
+        -0.9689 * xyz.x + 1.8758 * xyz.y + 0.0415 * xyz.z,
+        0.0557 * xyz.x - 0.2040 * xyz.y + 1.0570 * xyz.z
+    )
+}
@@ -1,11 +1,13 @@
-use std::sync::Arc;
+use std::{f64::consts::PI, sync::Arc};
 
 use crate::{
+    aabb::Aabb,
     hit::{Hit, HitRecord},
     material::Material,
+    onb::Onb,
     ray::Ray,
     v3,
-    vec3::Point,
+    vec3::{Point, Vec3},
 };
 
 pub struct Sphere {
@@ -25,29 +27,97 @@ impl Sphere {
 }
 
 impl Hit for Sphere {
-    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
-        let oc = ray.origin - self.center;
-        let a = ray.direction.len_squared();
-        let half_b = oc.dot(&ray.direction);
-        let c = oc.dot(&oc) - self.radius * self.radius;
-
-        let discriminant = half_b * half_b - a * c;
-        if discriminant > 0.0 {
-            let t1 = (-half_b - discriminant.sqrt()) / a;
-            let t2 = (-half_b + discriminant.sqrt()) / a;
-            if t1 < t_max && t1 > t_min {
-                let p = ray.at(t1);
-                let n = (p - self.center) / v3!(self.radius);
-                Some(HitRecord::new(ray, p, t1, n, &self.material))
-            } else if t2 < t_max && t2 > t_min {
-                let p = ray.at(t2);
-                let n = (p - self.center) / v3!(self.radius);
-                Some(HitRecord::new(ray, p, t2, n, &self.material))
-            } else {
-                None
-            }
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        hit_sphere(self.center, self.radius, &*self.material, ray, t_min, t_max)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let r = v3!(self.radius.abs());
+        Some(Aabb::new(self.center - r, self.center + r))
+    }
+
+    fn pdf_value(&self, origin: &Point, direction: &Vec3, _time: f64) -> f64 {
+        sphere_pdf_value(self.center, self.radius, origin, direction)
+    }
+
+    fn random_direction(&self, origin: &Point, _time: f64) -> Option<Vec3> {
+        Some(sphere_random_direction(self.center, self.radius, origin))
+    }
+
+    fn area(&self) -> f64 {
+        4.0 * PI * self.radius * self.radius
+    }
+}
+
+pub(crate) fn hit_sphere<'a>(
+    center: Point,
+    radius: f64,
+    material: &'a Material,
+    ray: &Ray,
+    t_min: f64,
+    t_max: f64,
+) -> Option<HitRecord<'a>> {
+    let oc = ray.origin - center;
+    let a = ray.direction.len_squared();
+    let half_b = oc.dot(&ray.direction);
+    let c = oc.dot(&oc) - radius * radius;
+
+    let discriminant = half_b * half_b - a * c;
+    if discriminant > 0.0 {
+        let t1 = (-half_b - discriminant.sqrt()) / a;
+        let t2 = (-half_b + discriminant.sqrt()) / a;
+        let t = if t1 < t_max && t1 > t_min {
+            t1
+        } else if t2 < t_max && t2 > t_min {
+            t2
         } else {
-            None
-        }
+            return None;
+        };
+        let p = ray.at(t);
+        let n = (p - center) / v3!(radius);
+        let mut rec = HitRecord::new(ray, p, t, n, material);
+        let (u, v) = sphere_uv(&n);
+        rec.u = u;
+        rec.v = v;
+        Some(rec)
+    } else {
+        None
+    }
+}
+
+// Uniform over the cone of directions subtending the sphere, or over all
+// directions when `origin` is inside it.
+pub(crate) fn sphere_pdf_value(
+    center: Point,
+    radius: f64,
+    origin: &Point,
+    direction: &Vec3,
+) -> f64 {
+    let to_center = center - *origin;
+    let distance_squared = to_center.len_squared();
+    if distance_squared <= radius * radius {
+        return 1.0 / (4.0 * PI);
     }
+    let cos_theta_max = (1.0 - radius * radius / distance_squared).sqrt();
+    if direction.unit().dot(&to_center.unit()) < cos_theta_max {
+        return 0.0;
+    }
+    1.0 / (2.0 * PI * (1.0 - cos_theta_max))
+}
+
+pub(crate) fn sphere_random_direction(center: Point, radius: f64, origin: &Point) -> Vec3 {
+    let direction = center - *origin;
+    let distance_squared = direction.len_squared();
+    if distance_squared <= radius * radius {
+        return Vec3::random_unit_vector();
+    }
+    Onb::new(&direction).local(&Vec3::random_to_sphere(radius, distance_squared))
+}
+
+// Maps a point on the unit sphere to u in [0, 1] around the y axis starting
+// from -x, and v in [0, 1] from -y to +y.
+pub fn sphere_uv(p: &Point) -> (f64, f64) {
+    let theta = (-p.y).clamp(-1.0, 1.0).acos();
+    let phi = (-p.z).atan2(p.x) + PI;
+    (phi / (2.0 * PI), theta / PI)
 }
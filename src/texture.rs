@@ -0,0 +1,182 @@
+use std::sync::Arc;
+
+use crate::{
+    color,
+    image::{Edge, Image},
+    perlin::Perlin,
+    vec3::{clamp, Color, Point},
+};
+
+pub trait Texture: Send + Sync {
+    fn value(&self, u: f64, v: f64, point: &Point) -> Color;
+}
+
+pub struct SolidColor {
+    color: Color,
+}
+
+impl SolidColor {
+    pub fn new(color: Color) -> Arc<Self> {
+        Arc::new(Self { color })
+    }
+}
+
+impl Texture for SolidColor {
+    fn value(&self, _u: f64, _v: f64, _point: &Point) -> Color {
+        self.color
+    }
+}
+
+pub struct CheckerTexture {
+    odd: Arc<dyn Texture>,
+    even: Arc<dyn Texture>,
+    size: f64,
+}
+
+impl CheckerTexture {
+    pub fn new(odd: Arc<dyn Texture>, even: Arc<dyn Texture>, size: f64) -> Arc<Self> {
+        Arc::new(Self { odd, even, size })
+    }
+
+    pub fn from_colors(odd: Color, even: Color, size: f64) -> Arc<Self> {
+        Self::new(SolidColor::new(odd), SolidColor::new(even), size)
+    }
+}
+
+impl Texture for CheckerTexture {
+    fn value(&self, u: f64, v: f64, point: &Point) -> Color {
+        let cell = (point.x / self.size).floor()
+            + (point.y / self.size).floor()
+            + (point.z / self.size).floor();
+        if cell.rem_euclid(2.0) < 1.0 {
+            self.even.value(u, v, point)
+        } else {
+            self.odd.value(u, v, point)
+        }
+    }
+}
+
+// Another texture multiplied by a constant colour.
+pub struct ScaledTexture {
+    texture: Arc<dyn Texture>,
+    scale: Color,
+}
+
+impl ScaledTexture {
+    pub fn new(texture: Arc<dyn Texture>, scale: Color) -> Arc<Self> {
+        Arc::new(Self { texture, scale })
+    }
+}
+
+impl Texture for ScaledTexture {
+    fn value(&self, u: f64, v: f64, point: &Point) -> Color {
+        self.scale * self.texture.value(u, v, point)
+    }
+}
+
+// One channel of another texture spread to all three, for scalar
+// parameters packed together into the channels of an image.
+pub struct ChannelTexture {
+    texture: Arc<dyn Texture>,
+    channel: usize,
+}
+
+impl ChannelTexture {
+    pub fn new(texture: Arc<dyn Texture>, channel: usize) -> Arc<Self> {
+        assert!(channel < 3, "texture channel out of range: {}", channel);
+        Arc::new(Self { texture, channel })
+    }
+}
+
+impl Texture for ChannelTexture {
+    fn value(&self, u: f64, v: f64, point: &Point) -> Color {
+        color!(self.texture.value(u, v, point)[self.channel])
+    }
+}
+
+pub struct ImageTexture {
+    image: Arc<Image>,
+    wrap_u: Edge,
+    wrap_v: Edge,
+}
+
+impl ImageTexture {
+    // Coordinates outside [0, 1] find the nearest edge.
+    pub fn new(image: Arc<Image>) -> Arc<Self> {
+        Self::with_wrap(image, Edge::Clamp, Edge::Clamp)
+    }
+
+    pub fn with_wrap(image: Arc<Image>, wrap_u: Edge, wrap_v: Edge) -> Arc<Self> {
+        Arc::new(Self {
+            image,
+            wrap_u,
+            wrap_v,
+        })
+    }
+}
+
+impl Texture for ImageTexture {
+    fn value(&self, u: f64, v: f64, _point: &Point) -> Color {
+        self.image
+            .bilinear_edges(u, 1.0 - v, self.wrap_u, self.wrap_v)
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub enum NoisePattern {
+    Noise,
+    Turbulence,
+    Fbm,
+    Marble,
+    Wood,
+}
+
+pub struct NoiseTexture {
+    perlin: Perlin,
+    pattern: NoisePattern,
+    color: Color,
+    scale: f64,
+    octaves: u32,
+}
+
+impl NoiseTexture {
+    pub fn new(perlin: Perlin, pattern: NoisePattern, scale: f64, octaves: u32) -> Arc<Self> {
+        Self::with_color(perlin, pattern, color!(1), scale, octaves)
+    }
+
+    pub fn with_color(
+        perlin: Perlin,
+        pattern: NoisePattern,
+        color: Color,
+        scale: f64,
+        octaves: u32,
+    ) -> Arc<Self> {
+        Arc::new(Self {
+            perlin,
+            pattern,
+            color,
+            scale,
+            octaves,
+        })
+    }
+}
+
+impl Texture for NoiseTexture {
+    fn value(&self, _u: f64, _v: f64, point: &Point) -> Color {
+        let p = self.scale * *point;
+        let value = match self.pattern {
+            NoisePattern::Noise => 0.5 * (1.0 + self.perlin.noise(&p)),
+            NoisePattern::Turbulence => self.perlin.turbulence(&p, self.octaves),
+            NoisePattern::Fbm => 0.5 * (1.0 + self.perlin.fbm(&p, self.octaves)),
+            NoisePattern::Marble => {
+                0.5 * (1.0 + (p.z + 10.0 * self.perlin.turbulence(&p, self.octaves)).sin())
+            }
+            NoisePattern::Wood => {
+                let rings =
+                    (p.x * p.x + p.z * p.z).sqrt() + 2.0 * self.perlin.turbulence(&p, self.octaves);
+                rings - rings.floor()
+            }
+        };
+        clamp(value, 0.0, 1.0) * self.color
+    }
+}
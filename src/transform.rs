@@ -0,0 +1,594 @@
+use std::ops;
+
+use crate::{
+    aabb::Aabb,
+    point, v3,
+    vec3::{Point, Vec3},
+};
+
+type Matrix = [[f64; 4]; 4];
+
+const IDENTITY: Matrix = [
+    [1.0, 0.0, 0.0, 0.0],
+    [0.0, 1.0, 0.0, 0.0],
+    [0.0, 0.0, 1.0, 0.0],
+    [0.0, 0.0, 0.0, 1.0],
+];
+
+// Row-major affine transform together with its inverse.
+#[derive(Clone, Copy, Debug)]
+pub struct Transform {
+    matrix: Matrix,
+    inverse: Matrix,
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Self::identity()
+    }
+}
+
+impl Transform {
+    pub fn identity() -> Self {
+        Self {
+            matrix: IDENTITY,
+            inverse: IDENTITY,
+        }
+    }
+
+    pub fn new(matrix: [[f64; 4]; 4]) -> Option<Self> {
+        let inverse = invert(&matrix)?;
+        Some(Self { matrix, inverse })
+    }
+
+    pub fn translate(offset: Vec3) -> Self {
+        let mut matrix = IDENTITY;
+        let mut inverse = IDENTITY;
+        for axis in 0..3 {
+            matrix[axis][3] = offset[axis];
+            inverse[axis][3] = -offset[axis];
+        }
+        Self { matrix, inverse }
+    }
+
+    // `None` when a factor is zero, which cannot be undone.
+    pub fn scale(factor: Vec3) -> Option<Self> {
+        let mut matrix = IDENTITY;
+        let mut inverse = IDENTITY;
+        for axis in 0..3 {
+            if factor[axis] == 0.0 {
+                return None;
+            }
+            matrix[axis][axis] = factor[axis];
+            inverse[axis][axis] = 1.0 / factor[axis];
+        }
+        Some(Self { matrix, inverse })
+    }
+
+    pub fn rotate(axis: Vec3, degrees: f64) -> Self {
+        let a = axis.unit();
+        let (sin, cos) = degrees.to_radians().sin_cos();
+        let t = 1.0 - cos;
+        let matrix = [
+            [
+                t * a.x * a.x + cos,
+                t * a.x * a.y - sin * a.z,
+                t * a.x * a.z + sin * a.y,
+                0.0,
+            ],
+            [
+                t * a.x * a.y + sin * a.z,
+                t * a.y * a.y + cos,
+                t * a.y * a.z - sin * a.x,
+                0.0,
+            ],
+            [
+                t * a.x * a.z - sin * a.y,
+                t * a.y * a.z + sin * a.x,
+                t * a.z * a.z + cos,
+                0.0,
+            ],
+            [0.0, 0.0, 0.0, 1.0],
+        ];
+        Self {
+            matrix,
+            inverse: transpose(&matrix),
+        }
+    }
+
+    pub fn rotate_x(degrees: f64) -> Self {
+        Self::rotate(v3!(1, 0, 0), degrees)
+    }
+
+    pub fn rotate_y(degrees: f64) -> Self {
+        Self::rotate(v3!(0, 1, 0), degrees)
+    }
+
+    pub fn rotate_z(degrees: f64) -> Self {
+        Self::rotate(v3!(0, 0, 1), degrees)
+    }
+
+    pub fn matrix(&self) -> &[[f64; 4]; 4] {
+        &self.matrix
+    }
+
+    // Of the linear part, how much it scales volumes.
+    pub fn determinant(&self) -> f64 {
+        let m = &self.matrix;
+        m[0][0] * (m[1][1] * m[2][2] - m[1][2] * m[2][1])
+            - m[0][1] * (m[1][0] * m[2][2] - m[1][2] * m[2][0])
+            + m[0][2] * (m[1][0] * m[2][1] - m[1][1] * m[2][0])
+    }
+
+    // For a rotation times a uniform scale, how much it scales areas.
+    pub(crate) fn area_scale(&self) -> Option<f64> {
+        let m = &self.matrix;
+        let column = |j: usize| v3!(m[0][j], m[1][j], m[2][j]);
+        let columns = [column(0), column(1), column(2)];
+        let scale = columns[0].len_squared();
+        let tolerance = 1e-9 * scale;
+        for i in 0..3 {
+            for j in 0..3 {
+                let expected = if i == j { scale } else { 0.0 };
+                if (columns[i].dot(&columns[j]) - expected).abs() > tolerance {
+                    return None;
+                }
+            }
+        }
+        Some(scale)
+    }
+
+    pub fn inverse(&self) -> Self {
+        Self {
+            matrix: self.inverse,
+            inverse: self.matrix,
+        }
+    }
+
+    pub fn point(&self, p: Point) -> Point {
+        let m = &self.matrix;
+        point!(
+            m[0][0] * p.x + m[0][1] * p.y + m[0][2] * p.z + m[0][3],
+            m[1][0] * p.x + m[1][1] * p.y + m[1][2] * p.z + m[1][3],
+            m[2][0] * p.x + m[2][1] * p.y + m[2][2] * p.z + m[2][3]
+        )
+    }
+
+    pub fn vector(&self, v: Vec3) -> Vec3 {
+        let m = &self.matrix;
+        v3!(
+            m[0][0] * v.x + m[0][1] * v.y + m[0][2] * v.z,
+            m[1][0] * v.x + m[1][1] * v.y + m[1][2] * v.z,
+            m[2][0] * v.x + m[2][1] * v.y + m[2][2] * v.z
+        )
+    }
+
+    // Normals go through the inverse transpose; the result is not normalized.
+    pub fn normal(&self, n: Vec3) -> Vec3 {
+        let m = &self.inverse;
+        v3!(
+            m[0][0] * n.x + m[1][0] * n.y + m[2][0] * n.z,
+            m[0][1] * n.x + m[1][1] * n.y + m[2][1] * n.z,
+            m[0][2] * n.x + m[1][2] * n.y + m[2][2] * n.z
+        )
+    }
+
+    pub fn bounding_box(&self, bbox: &Aabb) -> Aabb {
+        let corner = |i: usize| {
+            let pick = |axis: usize| {
+                if i & (1 << axis) == 0 {
+                    bbox.minimum[axis]
+                } else {
+                    bbox.maximum[axis]
+                }
+            };
+            let p = self.point(point!(pick(0), pick(1), pick(2)));
+            Aabb::new(p, p)
+        };
+        (1..8).fold(corner(0), |b, i| b.surrounding(&corner(i)))
+    }
+}
+
+// `a * b` applies `b` first, then `a`.
+impl ops::Mul for Transform {
+    type Output = Self;
+
+    fn mul(self, rhs: Self) -> Self::Output {
+        Self {
+            matrix: multiply(&self.matrix, &rhs.matrix),
+            inverse: multiply(&rhs.inverse, &self.inverse),
+        }
+    }
+}
+
+// Interpolates between two transforms over the shutter interval by
+// decomposing each into translation, rotation and scale. Shear is not
+// preserved.
+#[derive(Clone, Copy, Debug)]
+pub struct AnimatedTransform {
+    start: Transform,
+    end: Transform,
+    time0: f64,
+    time1: f64,
+    start_parts: Decomposed,
+    end_parts: Decomposed,
+}
+
+impl AnimatedTransform {
+    pub fn new(start: Transform, end: Transform, time0: f64, time1: f64) -> Self {
+        Self {
+            start,
+            end,
+            time0,
+            time1,
+            start_parts: Decomposed::new(&start),
+            end_parts: Decomposed::new(&end),
+        }
+    }
+
+    pub fn fixed(transform: Transform) -> Self {
+        Self::new(transform, transform, 0.0, 0.0)
+    }
+
+    pub fn is_animated(&self) -> bool {
+        self.time1 > self.time0 && self.start.matrix != self.end.matrix
+    }
+
+    pub fn at(&self, time: f64) -> Transform {
+        if !self.is_animated() || time <= self.time0 {
+            return self.start;
+        }
+        if time >= self.time1 {
+            return self.end;
+        }
+
+        let t = (time - self.time0) / (self.time1 - self.time0);
+        let (a, b) = (&self.start_parts, &self.end_parts);
+        // Going from a mirrored pose to an unmirrored one passes through a
+        // flat scale, where the nearer end stands in.
+        let scale = match Transform::scale(a.scale + t * (b.scale - a.scale)) {
+            Some(scale) => scale,
+            None if t < 0.5 => return self.start,
+            None => return self.end,
+        };
+        Transform::translate(a.translation + t * (b.translation - a.translation))
+            * a.rotation.slerp(&b.rotation, t).to_transform()
+            * scale
+    }
+
+    // Averaged over the two ends, which is exact when they agree.
+    pub(crate) fn area_scale(&self) -> Option<f64> {
+        Some(0.5 * (self.start.area_scale()? + self.end.area_scale()?))
+    }
+
+    // Rotations sweep arcs, so the box is the union of many sampled poses,
+    // padded by how far a corner can get from the nearest one.
+    pub fn bounding_box(&self, bbox: &Aabb) -> Aabb {
+        if !self.is_animated() {
+            return self.start.bounding_box(bbox);
+        }
+        let steps = 64;
+        let sampled = (1..=steps).fold(self.start.bounding_box(bbox), |b, i| {
+            let time = self.time0 + (self.time1 - self.time0) * f64::from(i) / f64::from(steps);
+            b.surrounding(&self.at(time).bounding_box(bbox))
+        });
+
+        // Translation and scale move corners at a constant rate and rotation
+        // turns them at a constant angular rate, so a corner moves at most
+        // `travel` over the interval.
+        let (a, b) = (&self.start_parts, &self.end_parts);
+        let max_abs = |v: Vec3| v.x.abs().max(v.y.abs()).max(v.z.abs());
+        let reach = (0..3)
+            .map(|axis| {
+                let extent = bbox.minimum[axis].abs().max(bbox.maximum[axis].abs());
+                extent * extent
+            })
+            .sum::<f64>()
+            .sqrt();
+        let angle = 2.0 * a.rotation.dot(&b.rotation).abs().min(1.0).acos();
+        let travel = (b.translation - a.translation).len()
+            + max_abs(b.scale - a.scale) * reach
+            + angle * max_abs(a.scale).max(max_abs(b.scale)) * reach;
+        let pad = v3!(travel / f64::from(2 * steps));
+        Aabb::new(sampled.minimum - pad, sampled.maximum + pad)
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Decomposed {
+    translation: Vec3,
+    rotation: Quaternion,
+    scale: Vec3,
+}
+
+impl Decomposed {
+    fn new(transform: &Transform) -> Self {
+        let m = &transform.matrix;
+        let translation = v3!(m[0][3], m[1][3], m[2][3]);
+        let mut columns = [0, 1, 2].map(|j| v3!(m[0][j], m[1][j], m[2][j]));
+        let mut scale = v3!(columns[0].len(), columns[1].len(), columns[2].len());
+        if columns[0].dot(&columns[1].cross(&columns[2])) < 0.0 {
+            scale.x = -scale.x;
+        }
+        for (axis, column) in columns.iter_mut().enumerate() {
+            *column = *column / scale[axis];
+        }
+        Self {
+            translation,
+            rotation: Quaternion::from_columns(&columns),
+            scale,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Quaternion {
+    w: f64,
+    v: Vec3,
+}
+
+impl Quaternion {
+    fn from_columns(c: &[Vec3; 3]) -> Self {
+        // Element (row, col) of the rotation matrix is c[col][row].
+        let trace = c[0].x + c[1].y + c[2].z;
+        let q = if trace > 0.0 {
+            let s = 0.5 / (trace + 1.0).sqrt();
+            Self {
+                w: 0.25 / s,
+                v: v3!(
+                    (c[1].z - c[2].y) * s,
+                    (c[2].x - c[0].z) * s,
+                    (c[0].y - c[1].x) * s
+                ),
+            }
+        } else if c[0].x > c[1].y && c[0].x > c[2].z {
+            let s = 2.0 * (1.0 + c[0].x - c[1].y - c[2].z).sqrt();
+            Self {
+                w: (c[1].z - c[2].y) / s,
+                v: v3!(0.25 * s, (c[1].x + c[0].y) / s, (c[2].x + c[0].z) / s),
+            }
+        } else if c[1].y > c[2].z {
+            let s = 2.0 * (1.0 + c[1].y - c[0].x - c[2].z).sqrt();
+            Self {
+                w: (c[2].x - c[0].z) / s,
+                v: v3!((c[1].x + c[0].y) / s, 0.25 * s, (c[2].y + c[1].z) / s),
+            }
+        } else {
+            let s = 2.0 * (1.0 + c[2].z - c[0].x - c[1].y).sqrt();
+            Self {
+                w: (c[0].y - c[1].x) / s,
+                v: v3!((c[2].x + c[0].z) / s, (c[2].y + c[1].z) / s, 0.25 * s),
+            }
+        };
+        q.normalized()
+    }
+
+    fn dot(&self, other: &Self) -> f64 {
+        self.w * other.w + self.v.dot(&other.v)
+    }
+
+    fn normalized(&self) -> Self {
+        let len = self.dot(self).sqrt();
+        Self {
+            w: self.w / len,
+            v: self.v / len,
+        }
+    }
+
+    fn slerp(&self, other: &Self, t: f64) -> Self {
+        let mut cos = self.dot(other);
+        let mut other = *other;
+        if cos < 0.0 {
+            cos = -cos;
+            other = Self {
+                w: -other.w,
+                v: -other.v,
+            };
+        }
+        if cos > 0.9995 {
+            return Self {
+                w: self.w + t * (other.w - self.w),
+                v: self.v + t * (other.v - self.v),
+            }
+            .normalized();
+        }
+
+        let theta = cos.acos();
+        let a = ((1.0 - t) * theta).sin() / theta.sin();
+        let b = (t * theta).sin() / theta.sin();
+        Self {
+            w: a * self.w + b * other.w,
+            v: a * self.v + b * other.v,
+        }
+    }
+
+    fn to_transform(self) -> Transform {
+        let (w, x, y, z) = (self.w, self.v.x, self.v.y, self.v.z);
+        let matrix = [
+            [
+                1.0 - 2.0 * (y * y + z * z),
+                2.0 * (x * y - w * z),
+                2.0 * (x * z + w * y),
+                0.0,
+            ],
+            [
+                2.0 * (x * y + w * z),
+                1.0 - 2.0 * (x * x + z * z),
+                2.0 * (y * z - w * x),
+                0.0,
+            ],
+            [
+                2.0 * (x * z - w * y),
+                2.0 * (y * z + w * x),
+                1.0 - 2.0 * (x * x + y * y),
+                0.0,
+            ],
+            [0.0, 0.0, 0.0, 1.0],
+        ];
+        Transform {
+            matrix,
+            inverse: transpose(&matrix),
+        }
+    }
+}
+
+fn multiply(a: &Matrix, b: &Matrix) -> Matrix {
+    let mut m = [[0.0; 4]; 4];
+    for (i, row) in m.iter_mut().enumerate() {
+        for (j, value) in row.iter_mut().enumerate() {
+            *value = (0..4).map(|k| a[i][k] * b[k][j]).sum();
+        }
+    }
+    m
+}
+
+fn transpose(a: &Matrix) -> Matrix {
+    let mut m = [[0.0; 4]; 4];
+    for (i, row) in m.iter_mut().enumerate() {
+        for (j, value) in row.iter_mut().enumerate() {
+            *value = a[j][i];
+        }
+    }
+    m
+}
+
+// Gauss-Jordan elimination with partial pivoting.
+fn invert(a: &Matrix) -> Option<Matrix> {
+    let mut m = *a;
+    let mut inv = IDENTITY;
+    for col in 0..4 {
+        let pivot = (col..4).max_by(|&i, &j| m[i][col].abs().total_cmp(&m[j][col].abs()))?;
+        if m[pivot][col].abs() < 1e-12 {
+            return None;
+        }
+        m.swap(col, pivot);
+        inv.swap(col, pivot);
+
+        let scale = 1.0 / m[col][col];
+        for j in 0..4 {
+            m[col][j] *= scale;
+            inv[col][j] *= scale;
+        }
+        for row in 0..4 {
+            if row != col {
+                let factor = m[row][col];
+                for j in 0..4 {
+                    m[row][j] -= factor * m[col][j];
+                    inv[row][j] -= factor * inv[col][j];
+                }
+            }
+        }
+    }
+    Some(inv)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(a: &Matrix, b: &Matrix) {
+        for i in 0..4 {
+            for j in 0..4 {
+                assert!((a[i][j] - b[i][j]).abs() < 1e-9, "{:?} != {:?}", a, b);
+            }
+        }
+    }
+
+    fn samples() -> Vec<Transform> {
+        let sheared = Transform::new([
+            [1.0, 0.5, 0.0, 2.0],
+            [0.0, 2.0, -0.3, 0.0],
+            [0.7, 0.0, 3.0, -1.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+        .unwrap();
+        vec![
+            Transform::identity(),
+            Transform::translate(v3!(1, -2, 3)),
+            Transform::scale(v3!(2, -0.5, 4)).unwrap(),
+            Transform::rotate(v3!(1, 2, 3), 37.0),
+            Transform::translate(v3!(1, 0, 0))
+                * Transform::rotate_x(-80.0)
+                * Transform::scale(v3!(1, 3, 1)).unwrap(),
+            sheared,
+        ]
+    }
+
+    #[test]
+    fn inverse_undoes_the_matrix() {
+        for transform in samples() {
+            let inverse = transform.inverse();
+            assert_close(&multiply(transform.matrix(), inverse.matrix()), &IDENTITY);
+            assert_close(&multiply(inverse.matrix(), transform.matrix()), &IDENTITY);
+            assert_close((transform * inverse).matrix(), &IDENTITY);
+            assert_close(
+                &invert(transform.matrix()).unwrap(),
+                transform.inverse().matrix(),
+            );
+        }
+    }
+
+    #[test]
+    fn singular_matrices_have_no_inverse() {
+        assert!(Transform::scale(v3!(1, 0, 1)).is_none());
+        assert!(Transform::new([
+            [1.0, 2.0, 3.0, 0.0],
+            [2.0, 4.0, 6.0, 0.0],
+            [0.0, 0.0, 1.0, 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+        .is_none());
+    }
+
+    #[test]
+    fn determinant_scales_volumes() {
+        let close = |a: f64, b: f64| (a - b).abs() < 1e-9;
+        assert!(close(Transform::translate(v3!(5, 6, 7)).determinant(), 1.0));
+        assert!(close(
+            Transform::rotate(v3!(1, 1, 0), 123.0).determinant(),
+            1.0
+        ));
+        assert!(close(
+            Transform::scale(v3!(2, -3, 4)).unwrap().determinant(),
+            -24.0
+        ));
+        for transform in samples() {
+            let product = transform.determinant() * transform.inverse().determinant();
+            assert!(close(product, 1.0));
+        }
+    }
+
+    #[test]
+    fn normals_stay_perpendicular() {
+        let tangent = v3!(1, -1, 0.5);
+        let normal = v3!(1, 1, 0);
+        for transform in samples() {
+            let dot = transform.vector(tangent).dot(&transform.normal(normal));
+            assert!(dot.abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn area_scale_only_for_similarities() {
+        let similar = Transform::rotate_y(30.0) * Transform::scale(v3!(-2, 2, 2)).unwrap();
+        assert!((similar.area_scale().unwrap() - 4.0).abs() < 1e-9);
+        assert!(Transform::scale(v3!(1, 2, 1))
+            .unwrap()
+            .area_scale()
+            .is_none());
+    }
+
+    #[test]
+    fn animation_interpolates_between_ends() {
+        let start = Transform::translate(v3!(0, 0, 0));
+        let end = Transform::translate(v3!(2, 4, 0)) * Transform::rotate_z(90.0);
+        let animated = AnimatedTransform::new(start, end, 1.0, 3.0);
+        assert_close(animated.at(0.0).matrix(), start.matrix());
+        assert_close(animated.at(5.0).matrix(), end.matrix());
+
+        let middle = animated.at(2.0);
+        let p = middle.point(point!(1, 0, 0));
+        let expected = point!(1.0 + 0.5f64.sqrt(), 2.0 + 0.5f64.sqrt(), 0.0);
+        assert!((p - expected).len() < 1e-9, "{:?}", p);
+    }
+}
@@ -0,0 +1,171 @@
+use std::sync::Arc;
+
+use crate::{
+    aabb::Aabb,
+    hit::{Hit, HitRecord},
+    material::Material,
+    pdf::area_to_solid_angle,
+    point,
+    random::Random,
+    ray::Ray,
+    v3,
+    vec3::{Point, Vec3},
+};
+
+pub struct Triangle {
+    vertices: [Point; 3],
+    normals: Option<[Vec3; 3]>,
+    uvs: Option<[(f64, f64); 3]>,
+    material: Arc<Material>,
+}
+
+impl Triangle {
+    pub fn new(vertices: [Point; 3], material: Arc<Material>) -> Box<Self> {
+        Self::with_attributes(vertices, None, None, material)
+    }
+
+    pub fn with_attributes(
+        vertices: [Point; 3],
+        normals: Option<[Vec3; 3]>,
+        uvs: Option<[(f64, f64); 3]>,
+        material: Arc<Material>,
+    ) -> Box<Self> {
+        Box::new(Self {
+            vertices,
+            normals,
+            uvs,
+            material,
+        })
+    }
+}
+
+impl Hit for Triangle {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        let (t, b1, b2) = intersect(&self.vertices, ray, t_min, t_max)?;
+        Some(record(
+            ray,
+            t,
+            v3!(1.0 - b1 - b2, b1, b2),
+            &self.vertices,
+            self.normals.as_ref(),
+            self.uvs.as_ref(),
+            &*self.material,
+        ))
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(bounds(&self.vertices))
+    }
+
+    fn pdf_value(&self, origin: &Point, direction: &Vec3, time: f64) -> f64 {
+        let ray = Ray::with_time(*origin, *direction, time);
+        match intersect(&self.vertices, &ray, 0.001, f64::INFINITY) {
+            Some((t, _, _)) => {
+                let [p0, p1, p2] = self.vertices;
+                let normal = (p1 - p0).cross(&(p2 - p0));
+                let area = 0.5 * normal.len();
+                let distance_squared = t * t * direction.len_squared();
+                let cosine = (direction.dot(&normal) / (direction.len() * normal.len())).abs();
+                area_to_solid_angle(distance_squared, cosine, area)
+            }
+            None => 0.0,
+        }
+    }
+
+    fn area(&self) -> f64 {
+        let [p0, p1, p2] = self.vertices;
+        0.5 * (p1 - p0).cross(&(p2 - p0)).len()
+    }
+
+    fn random_direction(&self, origin: &Point, _time: f64) -> Option<Vec3> {
+        // Uniform over the triangle's area.
+        let su = f64::random().sqrt();
+        let b1 = f64::random() * su;
+        let [p0, p1, p2] = self.vertices;
+        Some((1.0 - su) * p0 + b1 * p1 + (su - b1) * p2 - *origin)
+    }
+}
+
+// Möller–Trumbore, returning the distance and the barycentric weights of the
+// second and third vertices.
+pub fn intersect(
+    vertices: &[Point; 3],
+    ray: &Ray,
+    t_min: f64,
+    t_max: f64,
+) -> Option<(f64, f64, f64)> {
+    let [p0, p1, p2] = *vertices;
+    let e1 = p1 - p0;
+    let e2 = p2 - p0;
+
+    let pvec = ray.direction.cross(&e2);
+    let det = e1.dot(&pvec);
+    if det.abs() < 1e-12 {
+        return None;
+    }
+    let inv_det = 1.0 / det;
+
+    let tvec = ray.origin - p0;
+    let b1 = tvec.dot(&pvec) * inv_det;
+    if !(0.0..=1.0).contains(&b1) {
+        return None;
+    }
+
+    let qvec = tvec.cross(&e1);
+    let b2 = ray.direction.dot(&qvec) * inv_det;
+    if b2 < 0.0 || b1 + b2 > 1.0 {
+        return None;
+    }
+
+    let t = e2.dot(&qvec) * inv_det;
+    if t < t_max && t > t_min {
+        Some((t, b1, b2))
+    } else {
+        None
+    }
+}
+
+pub(crate) fn record<'a>(
+    ray: &Ray,
+    t: f64,
+    barycentric: Vec3,
+    vertices: &[Point; 3],
+    normals: Option<&[Vec3; 3]>,
+    uvs: Option<&[(f64, f64); 3]>,
+    material: &'a Material,
+) -> HitRecord<'a> {
+    let [p0, p1, p2] = *vertices;
+    let outward_normal = (p1 - p0).cross(&(p2 - p0)).unit();
+    let mut rec = HitRecord::new(ray, ray.at(t), t, outward_normal, material);
+
+    if let Some([n0, n1, n2]) = normals {
+        let n = (barycentric.x * *n0 + barycentric.y * *n1 + barycentric.z * *n2).unit();
+        rec.normal = if n.dot(&rec.normal) < 0.0 { -n } else { n };
+    }
+    let (u, v) = match uvs {
+        Some([uv0, uv1, uv2]) => (
+            barycentric.x * uv0.0 + barycentric.y * uv1.0 + barycentric.z * uv2.0,
+            barycentric.x * uv0.1 + barycentric.y * uv1.1 + barycentric.z * uv2.1,
+        ),
+        None => (barycentric.y, barycentric.z),
+    };
+    rec.u = u;
+    rec.v = v;
+    rec.barycentric = barycentric;
+    rec
+}
+
+pub(crate) fn bounds(vertices: &[Point; 3]) -> Aabb {
+    let [p0, p1, p2] = *vertices;
+    let minimum = point!(
+        p0.x.min(p1.x).min(p2.x),
+        p0.y.min(p1.y).min(p2.y),
+        p0.z.min(p1.z).min(p2.z)
+    );
+    let maximum = point!(
+        p0.x.max(p1.x).max(p2.x),
+        p0.y.max(p1.y).max(p2.y),
+        p0.z.max(p1.z).max(p2.z)
+    );
+    Aabb::new(minimum, maximum).padded(1e-4)
+}
@@ -1,4 +1,4 @@
-use std::ops;
+use std::{f64::consts::PI, ops};
 
 use crate::{random::Random, v3};
 
@@ -97,6 +97,26 @@ impl Vec3 {
         }
     }
 
+    // Cosine weighted direction around +z.
+    pub fn random_cosine_direction() -> Self {
+        let r1 = f64::random();
+        let r2 = f64::random();
+        let phi = 2.0 * PI * r1;
+        let z = (1.0 - r2).sqrt();
+        v3!(phi.cos() * r2.sqrt(), phi.sin() * r2.sqrt(), z)
+    }
+
+    // Uniform direction inside the cone around +z subtended by a sphere of
+    // `radius` at `distance_squared`.
+    pub fn random_to_sphere(radius: f64, distance_squared: f64) -> Self {
+        let r1 = f64::random();
+        let r2 = f64::random();
+        let z = 1.0 + r2 * ((1.0 - radius * radius / distance_squared).sqrt() - 1.0);
+        let phi = 2.0 * PI * r1;
+        let sin = (1.0 - z * z).max(0.0).sqrt();
+        v3!(phi.cos() * sin, phi.sin() * sin, z)
+    }
+
     pub fn is_near_zero(&self) -> bool {
         let s = 1e-8;
         self.x.abs() < s && self.y.abs() < s && self.z.abs() < s
@@ -112,6 +132,11 @@ impl Vec3 {
         let r_out_parallel = -(1.0 - r_out_perp.len_squared()).abs().sqrt() * *v;
         r_out_perp + r_out_parallel
     }
+
+    // Rec. 709 luminance of a linear color.
+    pub fn luminance(&self) -> f64 {
+        0.2126 * self.x + 0.7152 * self.y + 0.0722 * self.z
+    }
 }
 
 impl From<f64> for Vec3 {
@@ -248,6 +273,19 @@ impl ops::Neg for Vec3 {
     }
 }
 
+impl ops::Index<usize> for Vec3 {
+    type Output = f64;
+
+    fn index(&self, index: usize) -> &Self::Output {
+        match index {
+            0 => &self.x,
+            1 => &self.y,
+            2 => &self.z,
+            _ => panic!("Vec3 index out of range: {}", index),
+        }
+    }
+}
+
 impl ops::AddAssign for Vec3 {
     fn add_assign(&mut self, rhs: Self) {
         self.x += rhs.x;